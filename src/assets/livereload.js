@@ -1,11 +1,183 @@
-// The ws instantiation is made in `src/unveil.rs`
+// The reload socket is served next to this script, at the root of the public directory
+const public_url = new URL("./", document.currentScript.src);
+const socket_url = new URL("__livereload", public_url);
+socket_url.protocol = socket_url.protocol === "https:" ? "wss:" : "ws:";
+// Pages opened with the presenter key printed by `unveil serve` lead, the others follow them
+const presenter_key = new URLSearchParams(location.search).get("key");
+if (presenter_key) {
+    socket_url.searchParams.set("key", presenter_key);
+}
+const socket = new WebSocket(socket_url);
+
+// Presenter windows and the presenter page stay on the same slide,
+// the slides previewed inside the presenter page are driven by it instead
+const is_preview = window.self !== window.top;
+const deck = new URL("./", location.href).pathname;
+let remote_navigation = false;
+
+// Followers detach by navigating on their own, and re-sync with the last presenter position
+let following = !sessionStorage.getItem("unveil-detached");
+let presenter_position = null;
+
+// A page reloaded on the edited slide moves the other windows there, instead of being
+// sent back to the previous position replayed by the server
+let announced_slide = null;
+if (presenter_key && sessionStorage.getItem("unveil-announce")) {
+    sessionStorage.removeItem("unveil-announce");
+    announced_slide = slide_index_from_hash(location.hash);
+}
+
+socket.onopen = function () {
+    if (announced_slide !== null && !is_preview) {
+        socket.send(JSON.stringify({type: "navigate", deck, slide: announced_slide}));
+    }
+};
+
+document.addEventListener("unveil:slide", (event) => {
+    if (is_preview || remote_navigation) {
+        return;
+    }
+    if (!presenter_key) {
+        set_following(false);
+    } else if (socket.readyState === WebSocket.OPEN) {
+        socket.send(JSON.stringify({type: "navigate", deck, ...event.detail}));
+    }
+});
+
 socket.onmessage = function (event) {
-    if (event.data === "reload") {
+    const message = JSON.parse(event.data);
+    if (message.type === "error") {
+        show_build_error(message);
+        return;
+    }
+
+    if (message.type === "navigate") {
+        if (announced_slide !== null) {
+            if (message.deck === deck && message.slide === announced_slide) {
+                announced_slide = null;
+            }
+            return;
+        }
+        if (!is_preview && message.deck === deck) {
+            presenter_position = message;
+            if (presenter_key) {
+                follow();
+            } else {
+                set_following(following);
+            }
+        }
+        return;
+    }
+
+    hide_build_error();
+    if (message.type === "css") {
+        swap_stylesheets(message.stylesheets);
+    } else if (message.type === "reload") {
         socket.close();
+        // The page reopens on the slide in the url, the edited one when known
+        if (message.slide && !is_preview && document.querySelector(`section[data-name="${CSS.escape(message.slide)}"]`)) {
+            history.replaceState(null, "", "#/" + encodeURIComponent(message.slide));
+            if (presenter_key) {
+                sessionStorage.setItem("unveil-announce", "1");
+            }
+        }
         location.reload();
     }
 };
 
+const follow = () => {
+    const {slide, fragment} = presenter_position;
+    if (slide !== current_slide || fragment !== current_fragment) {
+        remote_navigation = true;
+        go_to_slide(slide, fragment);
+        remote_navigation = false;
+    }
+};
+
+// Followers see whether they follow the presenter, and can detach or re-sync
+const set_following = (follows) => {
+    if (presenter_key || !presenter_position) {
+        return;
+    }
+
+    following = follows;
+    if (following) {
+        sessionStorage.removeItem("unveil-detached");
+        follow();
+    } else {
+        sessionStorage.setItem("unveil-detached", "1");
+    }
+
+    let toggle = document.getElementById("unveil-follow");
+    if (!toggle) {
+        toggle = document.createElement("button");
+        toggle.id = "unveil-follow";
+        toggle.style.cssText = "position: fixed; right: 1em; bottom: 1em; z-index: 9999; padding: 0.4em 0.8em; "
+            + "border: none; border-radius: 1em; background: rgba(20, 20, 20, 0.7); color: white; "
+            + "font: 14px sans-serif; cursor: pointer;";
+        toggle.onclick = () => set_following(!following);
+        document.body.appendChild(toggle);
+    }
+    toggle.textContent = following ? "Following the presenter, detach" : "Detached, re-sync with the presenter";
+};
+
+// Reload the changed stylesheets in place, the current slide stays on screen
+const swap_stylesheets = (stylesheets) => {
+    const links = [...document.querySelectorAll('link[rel="stylesheet"]')];
+    const changed = stylesheets.map(path => {
+        const pathname = new URL("." + path, public_url).pathname;
+        return links.find(link => new URL(link.href).pathname === pathname);
+    });
+    // Imported stylesheets such as the project `theme.css` have no link to swap
+    if (changed.includes(undefined)) {
+        socket.close();
+        location.reload();
+        return;
+    }
+
+    changed.forEach(link => {
+        const url = new URL(link.href);
+
+        // The old stylesheet is removed once the new one is applied to avoid a flash of unstyled content
+        url.searchParams.set("v", Date.now().toString());
+        const updated = link.cloneNode();
+        updated.href = url.href;
+        updated.onload = () => link.remove();
+        link.after(updated);
+    });
+};
+
+// Failed builds are shown over the slides until the next successful one
+const show_build_error = (error) => {
+    let overlay = document.getElementById("unveil-build-error");
+    if (!overlay) {
+        overlay = document.createElement("div");
+        overlay.id = "unveil-build-error";
+        overlay.style.cssText = "position: fixed; inset: 0; z-index: 10000; overflow: auto; padding: 2em; "
+            + "background: rgba(20, 20, 20, 0.92); color: #ffb4b4; font: 16px/1.5 monospace; text-align: left;";
+        document.body.appendChild(overlay);
+    }
+
+    const title = document.createElement("div");
+    title.style.cssText = "color: white; font-weight: bold; margin-bottom: 1em;";
+    title.textContent = error.file
+        ? "Build failed in " + error.file + (error.line ? ":" + error.line : "")
+        : "Build failed";
+
+    const message = document.createElement("pre");
+    message.style.cssText = "white-space: pre-wrap; margin: 0;";
+    message.textContent = error.message;
+
+    overlay.replaceChildren(title, message);
+};
+
+const hide_build_error = () => {
+    const overlay = document.getElementById("unveil-build-error");
+    if (overlay) {
+        overlay.remove();
+    }
+};
+
 window.onbeforeunload = function () {
     socket.close();
-};
\ No newline at end of file
+};
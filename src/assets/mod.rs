@@ -3,8 +3,14 @@ pub static HIGHLIGHT_CSS: &[u8] = include_bytes!("highlight.css");
 pub static CSS_THEME: &[u8] = include_bytes!("theme.css");
 pub static CSS_DARK_THEME: &[u8] = include_bytes!("dark_theme.css");
 pub static CSS: &[u8] = include_bytes!("unveil.css");
+pub static SLIDES_CSS: &[u8] = include_bytes!("slides.css");
+pub static INDEX_CSS: &[u8] = include_bytes!("index.css");
+pub static PRINT_CSS: &[u8] = include_bytes!("print.css");
+pub static HANDOUT_CSS: &[u8] = include_bytes!("handout.css");
+pub static EPUB_CSS: &[u8] = include_bytes!("epub.css");
 pub static JS: &[u8] = include_bytes!("unveil.js");
 pub static LIVERELOAD_JS: &[u8] = include_bytes!("livereload.js");
+pub static PRESENTER_HTML: &[u8] = include_bytes!("presenter.html");
 pub static CLIPBOARD_JS: &[u8] = include_bytes!("clipboard.js");
 pub static LANDING: &[u8] = include_bytes!("landing.md");
 pub static FONT_AWESOME: &[u8] = include_bytes!("fontawesome/css/fontawesome.css");
@@ -1,9 +1,14 @@
 let current_slide = 0;
-
-hljs.configure({
-    tabReplace: '    ',
-    languages: []
-});
+// Number of fragments revealed on the current slide
+let current_fragment = 0;
+
+// highlight.js and clipboard.js are not bundled in release builds without code blocks
+if (typeof hljs !== "undefined") {
+    hljs.configure({
+        tabReplace: '    ',
+        languages: []
+    });
+}
 
 window.onresize = () => {
     const slide = get_slide(current_slide);
@@ -12,9 +17,11 @@ window.onresize = () => {
 
 window.onload = () => {
     // Highlight code blocks
-    Array
-        .from(document.querySelectorAll('code'))
-        .forEach(block => hljs.highlightBlock(block));
+    if (typeof hljs !== "undefined") {
+        Array
+            .from(document.querySelectorAll('code'))
+            .forEach(block => hljs.highlightBlock(block));
+    }
 
     // Add ClipBoardJS attr to clipboard buttons
     let clip_buttons = document.querySelectorAll('.btn-copy');
@@ -23,8 +30,16 @@ window.onload = () => {
         const code = code_block.innerText;
         clip_button.setAttribute("data-clipboard-text", code);
     });
+
+    // Reloads, bookmarks and shared links open the slide named in the url
+    go_to_hash();
 };
 
+window.addEventListener("hashchange", () => go_to_hash());
+
+// Lets the presenter view drive the slides it embeds
+window.document.addEventListener("unveil:go", e => go_to_slide(e.detail.slide, e.detail.fragment));
+
 window.document.addEventListener("keydown", e => {
     if (e.key === "ArrowLeft") {
         next_slide_left();
@@ -37,21 +52,74 @@ const get_slide = (idx) => {
     return document.getElementById("unveil-slide-" + idx);
 };
 
+// Elements with the `fragment` class are revealed one at a time before moving to the next slide
+const get_fragments = (idx) => {
+    return get_slide(idx).querySelectorAll(".fragment");
+};
+
+// Slides are addressed by their file name, `#/landing`, or their position from 1, `#/5`
+const slide_index_from_hash = (hash) => {
+    const route = decodeURIComponent(hash.replace(/^#\/?/, ""));
+    if (!route) {
+        return null;
+    }
+
+    const named = document.querySelector(`section[data-name="${CSS.escape(route)}"]`);
+    if (named) {
+        return parseInt(named.id.replace("unveil-slide-", ""));
+    }
+
+    const position = parseInt(route, 10);
+    return get_slide(position - 1) ? position - 1 : null;
+};
+
+const slide_hash = (idx) => {
+    const name = get_slide(idx).dataset.name;
+    return "#/" + (name ? encodeURIComponent(name) : idx + 1);
+};
+
+const go_to_hash = () => {
+    const idx = slide_index_from_hash(window.location.hash);
+    if (idx !== null && idx !== current_slide) {
+        go_to_slide(idx);
+    } else if (idx !== null) {
+        get_slide(idx).scrollIntoView();
+    }
+};
+
+const go_to_slide = (idx, fragment = 0) => {
+    const curr_slide = get_slide(current_slide);
+    const target = get_slide(idx);
+    if (target) {
+        const fragments = get_fragments(idx);
+        current_fragment = Math.min(fragment, fragments.length);
+        fragments.forEach((element, i) => element.classList.toggle("visible", i < current_fragment));
+
+        // Revealing a fragment keeps the slide in place
+        if (idx !== current_slide) {
+            current_slide = idx;
+            history.replaceState(null, "", slide_hash(idx));
+            transition_and_scroll(curr_slide, target);
+        }
+        // Lets the live-reload script keep the other windows on the same slide
+        document.dispatchEvent(new CustomEvent("unveil:slide", {detail: {slide: idx, fragment: current_fragment}}));
+    }
+};
+
 const next_slide_right = () => {
-    let curr_slide = get_slide(current_slide);
-    let right = get_slide(current_slide + 1);
-    if (right) {
-        current_slide++;
-        transition_and_scroll(curr_slide, right)
+    if (current_fragment < get_fragments(current_slide).length) {
+        go_to_slide(current_slide, current_fragment + 1);
+    } else {
+        go_to_slide(current_slide + 1);
     }
 };
 
+// Going back reveals the previous slide with all its fragments
 const next_slide_left = () => {
-    let curr_slide = get_slide(current_slide);
-    let left = get_slide(current_slide - 1);
-    if (left) {
-        current_slide--;
-        transition_and_scroll(curr_slide, left);
+    if (current_fragment > 0) {
+        go_to_slide(current_slide, current_fragment - 1);
+    } else if (get_slide(current_slide - 1)) {
+        go_to_slide(current_slide - 1, get_fragments(current_slide - 1).length);
     }
 };
 
@@ -136,10 +204,12 @@ const fetch_with_timeout = (code, url) => {
     return timeout(fetch_playpen)
 };
 
-let clipboard = new ClipboardJS('.btn-copy');
+if (typeof ClipboardJS !== "undefined") {
+    let clipboard = new ClipboardJS('.btn-copy');
 
-clipboard.on('success', function (e) {
-    e.trigger.classList.add("bounce-in-active");
-    setTimeout(() => e.trigger.classList.remove("bounce-in-active"), 300);
-    e.clearSelection();
-});
+    clipboard.on('success', function (e) {
+        e.trigger.classList.add("bounce-in-active");
+        setTimeout(() => e.trigger.classList.remove("bounce-in-active"), 300);
+        e.clearSelection();
+    });
+}
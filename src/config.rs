@@ -1,15 +1,154 @@
-use crate::assets::{CSS_DARK_THEME, CSS_THEME};
-use anyhow::{Error, Result};
+use crate::{
+    assets::{CSS_DARK_THEME, CSS_THEME},
+    unveil::BuildError,
+};
+use anyhow::{anyhow, Error, Result};
 use serde::{Deserialize, Serialize};
 use std::{fs::File, io::Read, path::Path, str::FromStr};
 
 #[derive(Serialize, Deserialize)]
 pub struct UnveilConfig {
-    name: String,
-    language: String,
+    pub name: String,
+    pub language: String,
+    #[serde(default)]
     pub slides: Vec<String>,
     pub gitignore: bool,
     pub theme: String,
+    /// Public url of the presentation, e.g. `/our-talk/` when deployed in a subdirectory
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub base_url: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub images: Option<ImageConfig>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub print: Option<PrintConfig>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub toc: Option<TocConfig>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub serve: Option<ServeConfig>,
+    /// Presentations of a multi-deck project, each one built in its own
+    /// `public` subdirectory. When empty `slides` are used as a single deck.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub decks: Vec<DeckConfig>,
+}
+
+/// Settings of the `{{toc}}` agenda slides
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq, Eq)]
+pub struct TocConfig {
+    /// Highlight the section following each agenda slide, when the agenda is repeated between chapters
+    #[serde(default)]
+    pub highlight_current: bool,
+}
+
+/// Settings of `unveil serve`
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct ServeConfig {
+    /// Open the presentation in the default browser, like `--no-open` when false
+    #[serde(default = "ServeConfig::default_open")]
+    pub open: bool,
+}
+
+impl ServeConfig {
+    fn default_open() -> bool {
+        true
+    }
+}
+
+/// Settings of the `print.html` page used to export slides to PDF
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct PrintConfig {
+    /// Slide aspect ratio, e.g. `16:9` or `4:3`
+    #[serde(default = "PrintConfig::default_aspect_ratio")]
+    pub aspect_ratio: String,
+    /// Print speaker notes below each slide
+    #[serde(default)]
+    pub notes: bool,
+}
+
+impl Default for PrintConfig {
+    fn default() -> Self {
+        PrintConfig {
+            aspect_ratio: PrintConfig::default_aspect_ratio(),
+            notes: false,
+        }
+    }
+}
+
+impl PrintConfig {
+    // Printed pages are as wide as a laptop screen so slides keep their on screen layout
+    const PAGE_WIDTH: u32 = 1280;
+
+    fn default_aspect_ratio() -> String {
+        "16:9".to_string()
+    }
+
+    /// Page width and height in css pixels
+    pub fn page_size(&self) -> Result<(u32, u32)> {
+        let invalid = || anyhow!("Invalid aspect ratio {}", self.aspect_ratio);
+        let (width, height) = self.aspect_ratio.split_once(':').ok_or_else(invalid)?;
+        let width: u32 = width.trim().parse().map_err(|_| invalid())?;
+        let height: u32 = height.trim().parse().map_err(|_| invalid())?;
+
+        if width == 0 || height == 0 {
+            return Err(invalid());
+        }
+
+        Ok((
+            PrintConfig::PAGE_WIDTH,
+            PrintConfig::PAGE_WIDTH * height / width,
+        ))
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct DeckConfig {
+    /// Deck directory name in both `slides` and `public`
+    pub name: String,
+    pub title: Option<String>,
+    pub author: Option<String>,
+    pub date: Option<String>,
+    #[serde(default)]
+    pub slides: Vec<String>,
+    /// Overrides the project theme for this deck
+    pub theme: Option<String>,
+}
+
+impl DeckConfig {
+    fn invalid_name(&self) -> String {
+        format!(
+            "Invalid deck name `{}`, expected a directory name without `/` nor `..`",
+            self.name
+        )
+    }
+}
+
+/// Build-time processing applied to the images referenced in slides,
+/// enabled by adding an `[images]` table to `unveil.toml`.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct ImageConfig {
+    /// Images wider than this are downscaled, keeping their aspect ratio
+    pub max_width: Option<u32>,
+    #[serde(default)]
+    pub format: ImageFormat,
+    /// Jpeg encoding quality, from 1 to 100
+    #[serde(default = "ImageConfig::default_quality")]
+    pub quality: u8,
+}
+
+impl ImageConfig {
+    fn default_quality() -> u8 {
+        80
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, Hash, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum ImageFormat {
+    /// Keep the source image format
+    #[default]
+    Original,
+    Webp,
+    Jpeg,
+    Png,
 }
 
 impl Default for UnveilConfig {
@@ -20,6 +159,12 @@ impl Default for UnveilConfig {
             slides: vec!["landing.md".into()],
             gitignore: true,
             theme: "default".to_string(),
+            base_url: None,
+            images: None,
+            print: None,
+            toc: None,
+            serve: None,
+            decks: vec![],
         }
     }
 }
@@ -28,28 +173,99 @@ impl FromStr for UnveilConfig {
     type Err = Error;
 
     fn from_str(src: &str) -> Result<Self> {
-        let toml = toml::from_str(src).expect("Error loading config");
-        Ok(toml)
+        let config: UnveilConfig = toml::from_str(src)?;
+        if let Some(deck) = config.invalid_deck() {
+            return Err(anyhow!(deck.invalid_name()));
+        }
+        Ok(config)
     }
 }
 
 impl UnveilConfig {
     pub fn from_disk<P: AsRef<Path>>(config_file: P) -> Result<UnveilConfig> {
         let mut buffer = String::new();
-        File::open(config_file)?.read_to_string(&mut buffer)?;
+        File::open(&config_file)?.read_to_string(&mut buffer)?;
+
+        let config: UnveilConfig = toml::from_str(&buffer).map_err(|err| {
+            // Toml errors end with their position, it is reported separately
+            let message = err.to_string();
+            let message = message.split(" at line ").next().unwrap_or_default();
+            BuildError::new(
+                config_file.as_ref().to_string_lossy(),
+                err.line_col().map(|(line, _)| line + 1),
+                message,
+            )
+        })?;
+
+        if let Some(deck) = config.invalid_deck() {
+            let line = buffer.lines().position(|line| {
+                line.trim_start().starts_with("name")
+                    && line.contains(&format!("\"{}\"", deck.name))
+            });
+            return Err(BuildError::new(
+                config_file.as_ref().to_string_lossy(),
+                line.map(|line| line + 1),
+                deck.invalid_name(),
+            )
+            .into());
+        }
 
-        UnveilConfig::from_str(&buffer)
+        Ok(config)
+    }
+
+    /// First deck whose name is not a single path segment, it would be built outside of `public`
+    fn invalid_deck(&self) -> Option<&DeckConfig> {
+        self.decks.iter().find(|deck| {
+            matches!(deck.name.as_str(), "" | "." | "..") || deck.name.contains(['/', '\\', '\0'])
+        })
+    }
+
+    /// Prefix prepended to every generated url, empty for relative urls
+    pub fn base_url(&self) -> String {
+        match self.base_url.as_deref() {
+            None | Some("") => String::new(),
+            Some(base_url) if base_url.ends_with('/') => base_url.to_string(),
+            Some(base_url) => format!("{}/", base_url),
+        }
     }
 
     pub fn get_theme(&self) -> Result<Vec<u8>> {
-        match self.theme.as_str() {
+        UnveilConfig::load_theme(&self.theme)
+    }
+
+    pub fn load_theme(theme: &str) -> Result<Vec<u8>> {
+        match theme {
             "default" => Ok(CSS_THEME.to_vec()),
             "dark" => Ok(CSS_DARK_THEME.to_vec()),
             custom_theme => {
-                let content = std::fs::read_to_string(format!("public/{}", custom_theme))?;
+                let path = format!("public/{}", custom_theme);
+                let content = std::fs::read_to_string(&path).map_err(|err| {
+                    BuildError::new(&path, None, format!("Unable to read theme, {}", err))
+                })?;
                 let bytes = content.as_bytes().to_owned();
                 Ok(bytes)
             }
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::config::UnveilConfig;
+
+    #[test]
+    fn should_reject_deck_names_outside_of_public() {
+        let config = |name: &str| {
+            format!(
+                "name = \"talks\"\nlanguage = \"EN\"\ngitignore = true\ntheme = \"default\"\n\n[[decks]]\nname = \"{}\"\n",
+                name
+            )
+        };
+
+        assert!(config("rust-intro").parse::<UnveilConfig>().is_ok());
+        for name in ["../x", "a/b", "..", ""] {
+            let err = config(name).parse::<UnveilConfig>().err().unwrap();
+            assert!(err.to_string().starts_with("Invalid deck name"), "{}", err);
+        }
+    }
+}
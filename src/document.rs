@@ -0,0 +1,517 @@
+use crate::html::{
+    toc::{self, Toc},
+    Slide,
+};
+use once_cell::sync::Lazy;
+use pulldown_cmark::{CodeBlockKind, Event, Options, Parser, Tag};
+use regex::Regex;
+
+/// Markdown extensions of the exported documents, pipe tables become real tables there while
+/// the web build keeps rendering them as text
+pub(crate) const MARKDOWN_OPTIONS: Options = Options::ENABLE_TABLES;
+
+// The `[class="..."]` markdown extension is meaningless outside the generated html
+pub(crate) static MD_CLASS_RE: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r#"\[class="[^"]*"\]"#).unwrap());
+
+/// Structured view of a deck, used by the exporters that do not render html
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Document {
+    pub title: String,
+    pub language: String,
+    pub author: Option<String>,
+    pub date: Option<String>,
+    pub slides: Vec<DocumentSlide>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DocumentSlide {
+    /// Markdown file the slide was parsed from
+    pub source: String,
+    /// Slide markdown, without its stylematter and notes
+    pub markdown: String,
+    pub notes_markdown: Option<String>,
+    pub blocks: Vec<Block>,
+    pub notes: Vec<Block>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Block {
+    Heading {
+        level: u32,
+        content: Vec<Span>,
+    },
+    Paragraph(Vec<Span>),
+    Quote(Vec<Span>),
+    List {
+        ordered: bool,
+        items: Vec<ListItem>,
+    },
+    Code {
+        language: Option<String>,
+        code: String,
+    },
+    Image {
+        src: String,
+        alt: String,
+    },
+    Table {
+        header: Row,
+        rows: Vec<Row>,
+    },
+    Rule,
+}
+
+/// A table row, with the content of each cell
+pub type Row = Vec<Vec<Span>>;
+
+/// A list entry, nested lists are flattened and keep their nesting level
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ListItem {
+    pub level: usize,
+    pub content: Vec<Span>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Span {
+    pub text: String,
+    pub bold: bool,
+    pub italic: bool,
+    pub code: bool,
+    pub link: Option<String>,
+}
+
+impl Document {
+    /// Parse the slides from their source path and markdown content
+    pub fn parse(title: &str, language: &str, slides: &[(String, String)]) -> Document {
+        let parsed: Vec<Slide> = slides
+            .iter()
+            .map(|(_, markdown)| Slide::parse(markdown))
+            .collect();
+        let toc = Toc::new(
+            &parsed
+                .iter()
+                .map(|slide| slide.content.clone())
+                .collect::<Vec<String>>(),
+        );
+
+        let slides = slides
+            .iter()
+            .zip(parsed)
+            .map(|((source, _), slide)| {
+                // Agenda slides list the slide titles
+                let markdown = if toc::has_placeholder(&slide.content) {
+                    toc::expand_placeholder(&slide.content, &toc.markdown())
+                } else {
+                    slide.content
+                };
+                DocumentSlide {
+                    source: source.to_owned(),
+                    blocks: parse_blocks(&markdown),
+                    notes: slide.notes.as_deref().map(parse_blocks).unwrap_or_default(),
+                    markdown,
+                    notes_markdown: slide.notes,
+                }
+            })
+            .collect();
+
+        Document {
+            title: title.to_owned(),
+            language: language.to_owned(),
+            author: None,
+            date: None,
+            slides,
+        }
+    }
+}
+
+impl DocumentSlide {
+    /// Text of the first heading of the slide
+    pub fn title(&self) -> Option<String> {
+        self.heading().map(|(_, title)| title)
+    }
+
+    /// Level and text of the first heading of the slide
+    pub fn heading(&self) -> Option<(u32, String)> {
+        first_heading(&self.markdown)
+    }
+}
+
+impl Block {
+    pub fn plain_text(&self) -> String {
+        match self {
+            Block::Heading { content, .. } | Block::Paragraph(content) | Block::Quote(content) => {
+                plain_text(content)
+            }
+            Block::List { items, .. } => items
+                .iter()
+                .map(|item| plain_text(&item.content))
+                .collect::<Vec<String>>()
+                .join("\n"),
+            Block::Code { code, .. } => code.clone(),
+            Block::Image { alt, .. } => alt.clone(),
+            Block::Table { header, rows } => std::iter::once(header)
+                .chain(rows.iter())
+                .map(|row| {
+                    row.iter()
+                        .map(|cell| plain_text(cell))
+                        .collect::<Vec<String>>()
+                        .join(" | ")
+                })
+                .collect::<Vec<String>>()
+                .join("\n"),
+            Block::Rule => String::new(),
+        }
+    }
+}
+
+/// Level and text of the first heading of a slide, shared by the agenda, the manifest
+/// and the exported documents so their titles never differ
+pub fn first_heading(markdown: &str) -> Option<(u32, String)> {
+    let mut heading: Option<(u32, String)> = None;
+
+    for event in Parser::new_ext(markdown, Options::empty()) {
+        match event {
+            Event::Start(Tag::Heading(level, ..)) => heading = Some((level as u32, String::new())),
+            Event::End(Tag::Heading(..)) => {
+                return heading.map(|(level, title)| {
+                    (
+                        level,
+                        MD_CLASS_RE.replace_all(&title, "").trim().to_string(),
+                    )
+                })
+            }
+            Event::Text(text) | Event::Code(text) => {
+                if let Some((_, title)) = heading.as_mut() {
+                    title.push_str(&text);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    None
+}
+
+pub fn plain_text(spans: &[Span]) -> String {
+    spans.iter().map(|span| span.text.as_str()).collect()
+}
+
+/// Parse markdown into blocks, with the same options as `HtmlBuilder`
+pub fn parse_blocks(markdown: &str) -> Vec<Block> {
+    let mut blocks = vec![];
+    // Images are rendered as their own block after the paragraph containing them
+    let mut images = vec![];
+    let mut spans: Vec<Span> = vec![];
+    let mut style = Span::default();
+    let mut image: Option<(String, String)> = None;
+    let mut code: Option<(Option<String>, String)> = None;
+    let mut list_stack: Vec<bool> = vec![];
+    let mut items: Vec<ListItem> = vec![];
+    let mut quote_depth = 0;
+    let mut row: Row = vec![];
+    let mut table: Option<(Row, Vec<Row>)> = None;
+
+    for event in Parser::new_ext(markdown, MARKDOWN_OPTIONS) {
+        match event {
+            Event::Start(Tag::Emphasis) => style.italic = true,
+            Event::End(Tag::Emphasis) => style.italic = false,
+            Event::Start(Tag::Strong) => style.bold = true,
+            Event::End(Tag::Strong) => style.bold = false,
+            Event::Start(Tag::Link(_, url, _)) => style.link = Some(url.to_string()),
+            Event::End(Tag::Link(..)) => style.link = None,
+            Event::Start(Tag::Image(_, src, _)) => image = Some((src.to_string(), String::new())),
+            Event::End(Tag::Image(..)) => {
+                if let Some((src, alt)) = image.take() {
+                    images.push(Block::Image { src, alt });
+                }
+            }
+            Event::Start(Tag::CodeBlock(kind)) => {
+                let language = match kind {
+                    CodeBlockKind::Fenced(info) => info
+                        .split_whitespace()
+                        .next()
+                        .map(|language| language.to_string()),
+                    CodeBlockKind::Indented => None,
+                };
+                code = Some((language, String::new()));
+            }
+            Event::End(Tag::CodeBlock(_)) => {
+                if let Some((language, code)) = code.take() {
+                    blocks.push(Block::Code { language, code });
+                }
+            }
+            Event::Start(Tag::List(start)) => {
+                // A nested list ends the text of its parent item
+                if !list_stack.is_empty() && !spans.is_empty() {
+                    items.push(ListItem {
+                        level: list_stack.len() - 1,
+                        content: take_spans(&mut spans),
+                    });
+                }
+                list_stack.push(start.is_some());
+            }
+            Event::End(Tag::List(_)) => {
+                let ordered = list_stack.pop().unwrap_or_default();
+                if list_stack.is_empty() {
+                    blocks.push(Block::List {
+                        ordered,
+                        items: std::mem::take(&mut items),
+                    });
+                    blocks.append(&mut images);
+                }
+            }
+            Event::End(Tag::Item) if !spans.is_empty() => {
+                items.push(ListItem {
+                    level: list_stack.len().saturating_sub(1),
+                    content: take_spans(&mut spans),
+                });
+            }
+            Event::Start(Tag::BlockQuote) => quote_depth += 1,
+            Event::End(Tag::BlockQuote) => quote_depth -= 1,
+            Event::End(Tag::Heading(level, ..)) => {
+                blocks.push(Block::Heading {
+                    level: level as u32,
+                    content: take_spans(&mut spans),
+                });
+                blocks.append(&mut images);
+            }
+            Event::End(Tag::Paragraph) if list_stack.is_empty() => {
+                if !spans.is_empty() {
+                    let content = take_spans(&mut spans);
+                    if quote_depth > 0 {
+                        blocks.push(Block::Quote(content));
+                    } else {
+                        blocks.push(Block::Paragraph(content));
+                    }
+                }
+                blocks.append(&mut images);
+            }
+            Event::Start(Tag::Table(_)) => table = Some((vec![], vec![])),
+            Event::End(Tag::TableCell) => row.push(take_spans(&mut spans)),
+            Event::End(Tag::TableHead) => {
+                if let Some((header, _)) = table.as_mut() {
+                    *header = std::mem::take(&mut row);
+                }
+            }
+            Event::End(Tag::TableRow) => {
+                if let Some((_, rows)) = table.as_mut() {
+                    rows.push(std::mem::take(&mut row));
+                }
+            }
+            Event::End(Tag::Table(_)) => {
+                if let Some((header, rows)) = table.take() {
+                    blocks.push(Block::Table { header, rows });
+                }
+                blocks.append(&mut images);
+            }
+            Event::Rule => blocks.push(Block::Rule),
+            Event::Text(text) => {
+                if let Some((_, code)) = code.as_mut() {
+                    code.push_str(&text);
+                } else if let Some((_, alt)) = image.as_mut() {
+                    alt.push_str(&text);
+                } else {
+                    push_span(&mut spans, &style, &text, false);
+                }
+            }
+            Event::Code(text) => push_span(&mut spans, &style, &text, true),
+            Event::SoftBreak | Event::HardBreak => push_span(&mut spans, &style, " ", false),
+            _ => {}
+        }
+    }
+
+    blocks
+}
+
+fn push_span(spans: &mut Vec<Span>, style: &Span, text: &str, code: bool) {
+    // Merge with the previous span when the style does not change
+    if let Some(last) = spans.last_mut() {
+        if last.bold == style.bold
+            && last.italic == style.italic
+            && last.code == code
+            && last.link == style.link
+        {
+            last.text.push_str(text);
+            return;
+        }
+    }
+
+    spans.push(Span {
+        text: text.to_owned(),
+        code,
+        ..style.clone()
+    });
+}
+
+/// Take the accumulated spans, dropping the `[class="..."]` markdown extension
+fn take_spans(spans: &mut Vec<Span>) -> Vec<Span> {
+    let mut spans: Vec<Span> = std::mem::take(spans)
+        .into_iter()
+        .map(|mut span| {
+            if !span.code {
+                span.text = MD_CLASS_RE.replace_all(&span.text, "").to_string();
+            }
+            span
+        })
+        .filter(|span| !span.text.is_empty())
+        .collect();
+
+    if let Some(first) = spans.first_mut() {
+        first.text = first.text.trim_start().to_string();
+    }
+
+    if let Some(last) = spans.last_mut() {
+        last.text = last.text.trim_end().to_string();
+    }
+
+    spans
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        document::{parse_blocks, Block, Document, ListItem, Span},
+        html::HtmlBuilder,
+    };
+
+    fn text(text: &str) -> Span {
+        Span {
+            text: text.into(),
+            ..Span::default()
+        }
+    }
+
+    #[test]
+    fn should_parse_slide_blocks() {
+        let markdown = r#"# Hello [class="red"]
+
+Some **bold** text ![ferris](ferris.png)
+
+- one
+    - nested
+- two
+
+```rust
+fn main() {}
+```
+
+| a | b |
+|---|---|
+| 1 | 2 |
+"#;
+
+        let blocks = parse_blocks(markdown);
+
+        assert_eq!(
+            blocks,
+            vec![
+                Block::Heading {
+                    level: 1,
+                    content: vec![text("Hello")]
+                },
+                Block::Paragraph(vec![
+                    text("Some "),
+                    Span {
+                        text: "bold".into(),
+                        bold: true,
+                        ..Span::default()
+                    },
+                    text(" text")
+                ]),
+                Block::Image {
+                    src: "ferris.png".into(),
+                    alt: "ferris".into()
+                },
+                Block::List {
+                    ordered: false,
+                    items: vec![
+                        ListItem {
+                            level: 0,
+                            content: vec![text("one")]
+                        },
+                        ListItem {
+                            level: 1,
+                            content: vec![text("nested")]
+                        },
+                        ListItem {
+                            level: 0,
+                            content: vec![text("two")]
+                        },
+                    ]
+                },
+                Block::Code {
+                    language: Some("rust".into()),
+                    code: "fn main() {}\n".into()
+                },
+                Block::Table {
+                    header: vec![vec![text("a")], vec![text("b")]],
+                    rows: vec![vec![vec![text("1")], vec![text("2")]]]
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn should_parse_notes_and_title() {
+        let document = Document::parse(
+            "demo",
+            "EN",
+            &[("slides/a.md".into(), "## Title\n???\nSome notes".into())],
+        );
+
+        let slide = &document.slides[0];
+        assert_eq!(slide.title().as_deref(), Some("Title"));
+        assert_eq!(
+            slide.notes,
+            vec![Block::Paragraph(vec![text("Some notes")])]
+        );
+    }
+
+    #[test]
+    fn should_expand_agenda_in_documents() {
+        let slides: Vec<(String, String)> = [
+            "# Agenda\n\n{{toc}}\n\nWrite `{{toc}}` to list them",
+            "# Intro [class=\"red\"]",
+            "## Why *not*",
+        ]
+        .iter()
+        .map(|markdown| ("slides/a.md".to_string(), markdown.to_string()))
+        .collect();
+
+        let document = Document::parse("demo", "EN", &slides);
+
+        let agenda = &document.slides[0];
+        assert!(agenda.markdown.contains("- Intro\n  - Why not\n"));
+        assert_eq!(
+            agenda.blocks[1],
+            Block::List {
+                ordered: false,
+                items: vec![
+                    ListItem {
+                        level: 0,
+                        content: vec![text("Intro")]
+                    },
+                    ListItem {
+                        level: 1,
+                        content: vec![text("Why not")]
+                    },
+                ]
+            }
+        );
+        assert!(matches!(&agenda.blocks[2], Block::Paragraph(spans) if spans[1].text == "{{toc}}"));
+        assert_eq!(document.slides[1].title().as_deref(), Some("Intro"));
+    }
+
+    #[test]
+    fn should_only_render_tables_in_exported_documents() {
+        let markdown = "| Name | Owner |\n|------|-------|\n| a | main |\n";
+
+        assert!(matches!(
+            parse_blocks(markdown).as_slice(),
+            [Block::Table { rows, .. }] if rows.len() == 1
+        ));
+        // Decks written before tables keep their pipes as text
+        assert!(!HtmlBuilder::render_slide(markdown).contains("<table>"));
+    }
+}
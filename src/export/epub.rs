@@ -0,0 +1,470 @@
+use crate::{
+    assets::EPUB_CSS,
+    document::{Document, MARKDOWN_OPTIONS, MD_CLASS_RE},
+    export::{language_tag, lookup_image, xml_escape},
+};
+use anyhow::Result;
+use once_cell::sync::Lazy;
+use pulldown_cmark::{html, CodeBlockKind, Event, Parser, Tag};
+use regex::Regex;
+use std::{
+    fs,
+    io::{Seek, Write},
+    path::PathBuf,
+    time::{SystemTime, UNIX_EPOCH},
+};
+use syntect::{
+    highlighting::{Theme, ThemeSet},
+    html::highlighted_html_for_string,
+    parsing::SyntaxSet,
+};
+use zip::{write::SimpleFileOptions, CompressionMethod, ZipWriter};
+
+const MIME_TYPE: &str = "application/epub+zip";
+const XML_HEADER: &str = r#"<?xml version="1.0" encoding="UTF-8"?>"#;
+// Light theme, most e-readers display black text on a white page
+const CODE_THEME: &str = "InspiredGitHub";
+const CONTAINER: &str = r#"<?xml version="1.0" encoding="UTF-8"?><container version="1.0" xmlns="urn:oasis:names:tc:opendocument:xmlns:container"><rootfiles><rootfile full-path="OEBPS/content.opf" media-type="application/oebps-package+xml"/></rootfiles></container>"#;
+
+// `dc:date` only accepts W3C dates
+static DATE_RE: Lazy<Regex> = Lazy::new(|| Regex::new(r"^\d{4}(-\d{2}){0,2}$").unwrap());
+
+/// A chapter of the book, one per slide
+struct Chapter {
+    file: String,
+    title: String,
+    body: String,
+}
+
+/// A local image embedded in the book
+struct EpubImage {
+    src: String,
+    path: PathBuf,
+    file: String,
+    media_type: &'static str,
+}
+
+/// Render slides to xhtml, collecting the images they use
+struct Epub {
+    syntaxes: SyntaxSet,
+    theme: Theme,
+    images: Vec<EpubImage>,
+}
+
+/// Write the document as an EPUB 3 book
+pub fn write<W: Write + Seek>(document: &Document, writer: W) -> Result<()> {
+    let mut themes = ThemeSet::load_defaults();
+    let mut epub = Epub {
+        syntaxes: SyntaxSet::load_defaults_newlines(),
+        theme: themes.themes.remove(CODE_THEME).unwrap_or_default(),
+        images: vec![],
+    };
+
+    let chapters: Vec<Chapter> = document
+        .slides
+        .iter()
+        .enumerate()
+        .map(|(idx, slide)| {
+            let mut body = epub.render(&slide.markdown);
+            if let Some(notes) = &slide.notes_markdown {
+                body.push_str(&format!(
+                    r#"<div class="notes">{}</div>"#,
+                    epub.render(notes)
+                ));
+            }
+
+            Chapter {
+                file: format!("slide-{}.xhtml", idx + 1),
+                title: slide
+                    .title()
+                    .unwrap_or_else(|| format!("Slide {}", idx + 1)),
+                body,
+            }
+        })
+        .collect();
+
+    let mut zip = ZipWriter::new(writer);
+    let options = SimpleFileOptions::default().compression_method(CompressionMethod::Deflated);
+
+    // The mime type must be the first entry, uncompressed
+    zip.start_file(
+        "mimetype",
+        SimpleFileOptions::default().compression_method(CompressionMethod::Stored),
+    )?;
+    zip.write_all(MIME_TYPE.as_bytes())?;
+
+    zip.start_file("META-INF/container.xml", options)?;
+    zip.write_all(CONTAINER.as_bytes())?;
+    zip.start_file("OEBPS/content.opf", options)?;
+    zip.write_all(package(document, &chapters, &epub.images, now()).as_bytes())?;
+    zip.start_file("OEBPS/nav.xhtml", options)?;
+    zip.write_all(navigation(document, &chapters).as_bytes())?;
+    zip.start_file("OEBPS/style.css", options)?;
+    zip.write_all(EPUB_CSS)?;
+
+    for chapter in chapters.iter() {
+        zip.start_file(format!("OEBPS/text/{}", chapter.file), options)?;
+        let page = xhtml(
+            &document.language,
+            &chapter.title,
+            "../style.css",
+            &format!(r#"<section epub:type="chapter">{}</section>"#, chapter.body),
+        );
+        zip.write_all(page.as_bytes())?;
+    }
+
+    for image in epub.images.iter() {
+        zip.start_file(format!("OEBPS/images/{}", image.file), options)?;
+        zip.write_all(&fs::read(&image.path)?)?;
+    }
+
+    zip.finish()?;
+    Ok(())
+}
+
+impl Epub {
+    /// Render markdown to xhtml, code blocks being highlighted with inline styles
+    fn render(&mut self, markdown: &str) -> String {
+        let markdown = MD_CLASS_RE.replace_all(markdown, "");
+        let mut events = vec![];
+        // Language and content of the current code block
+        let mut code_block: Option<(Option<String>, String)> = None;
+        // Embedded file and alt text of the current image
+        let mut image: Option<(Option<String>, String)> = None;
+
+        for event in Parser::new_ext(&markdown, MARKDOWN_OPTIONS) {
+            match event {
+                Event::Start(Tag::CodeBlock(kind)) => {
+                    let language = match kind {
+                        CodeBlockKind::Fenced(info) => info
+                            .split_whitespace()
+                            .next()
+                            .map(|language| language.to_string()),
+                        CodeBlockKind::Indented => None,
+                    };
+                    code_block = Some((language, String::new()));
+                }
+                Event::End(Tag::CodeBlock(_)) => {
+                    if let Some((language, code)) = code_block.take() {
+                        events.push(Event::Html(
+                            self.highlight(language.as_deref(), &code).into(),
+                        ));
+                    }
+                }
+                Event::Start(Tag::Image(_, src, _)) => {
+                    image = Some((self.embed(&src), String::new()))
+                }
+                Event::End(Tag::Image(..)) => {
+                    if let Some((file, alt)) = image.take() {
+                        let html = match file {
+                            Some(file) => format!(
+                                r#"<img src="../images/{}" alt="{}"/>"#,
+                                file,
+                                xml_escape(&alt)
+                            ),
+                            None => format!("<em>{}</em>", xml_escape(&alt)),
+                        };
+                        events.push(Event::Html(html.into()));
+                    }
+                }
+                Event::Text(text) | Event::Code(text)
+                    if code_block.is_some() || image.is_some() =>
+                {
+                    if let Some((_, code)) = code_block.as_mut() {
+                        code.push_str(&text);
+                    } else if let Some((_, alt)) = image.as_mut() {
+                        alt.push_str(&text);
+                    }
+                }
+                // Raw html is not guaranteed to be well formed xhtml
+                Event::Html(_) => {}
+                _ if image.is_some() => {}
+                event => events.push(event),
+            }
+        }
+
+        let mut xhtml = String::new();
+        html::push_html(&mut xhtml, events.into_iter());
+        xhtml
+    }
+
+    fn highlight(&self, language: Option<&str>, code: &str) -> String {
+        let syntax = language
+            .and_then(|language| self.syntaxes.find_syntax_by_token(language))
+            .unwrap_or_else(|| self.syntaxes.find_syntax_plain_text());
+
+        highlighted_html_for_string(code, &self.syntaxes, syntax, &self.theme)
+            .unwrap_or_else(|_| format!("<pre><code>{}</code></pre>", xml_escape(code)))
+    }
+
+    /// File name of the image in the book, `None` when it is not a local image e-readers support
+    fn embed(&mut self, src: &str) -> Option<String> {
+        if let Some(image) = self.images.iter().find(|image| image.src == src) {
+            return Some(image.file.clone());
+        }
+
+        let path = lookup_image(src)?;
+        let (extension, media_type) = match path
+            .extension()
+            .and_then(|extension| extension.to_str())
+            .map(|extension| extension.to_lowercase())
+            .as_deref()
+        {
+            Some("png") => ("png", "image/png"),
+            Some("jpg") | Some("jpeg") => ("jpeg", "image/jpeg"),
+            Some("gif") => ("gif", "image/gif"),
+            Some("webp") => ("webp", "image/webp"),
+            Some("svg") => ("svg", "image/svg+xml"),
+            _ => return None,
+        };
+
+        let file = format!("image-{}.{}", self.images.len() + 1, extension);
+        self.images.push(EpubImage {
+            src: src.to_string(),
+            path,
+            file: file.clone(),
+            media_type,
+        });
+        Some(file)
+    }
+}
+
+fn package(
+    document: &Document,
+    chapters: &[Chapter],
+    images: &[EpubImage],
+    modified: u64,
+) -> String {
+    let language = language_tag(&document.language);
+    let identifier: String = document
+        .title
+        .to_lowercase()
+        .chars()
+        .map(|char| if char.is_alphanumeric() { char } else { '-' })
+        .collect();
+
+    let mut metadata = format!(
+        r#"<dc:identifier id="book-id">urn:unveil:{}</dc:identifier><dc:title>{}</dc:title><dc:language>{}</dc:language>"#,
+        xml_escape(&identifier),
+        xml_escape(&document.title),
+        xml_escape(&language)
+    );
+
+    if let Some(author) = &document.author {
+        metadata.push_str(&format!("<dc:creator>{}</dc:creator>", xml_escape(author)));
+    }
+
+    if let Some(date) = document
+        .date
+        .as_deref()
+        .filter(|date| DATE_RE.is_match(date))
+    {
+        metadata.push_str(&format!("<dc:date>{}</dc:date>", date));
+    }
+
+    metadata.push_str(&format!(
+        r#"<meta property="dcterms:modified">{}</meta>"#,
+        timestamp(modified)
+    ));
+
+    let mut manifest = String::from(
+        r#"<item id="nav" href="nav.xhtml" media-type="application/xhtml+xml" properties="nav"/><item id="style" href="style.css" media-type="text/css"/>"#,
+    );
+    let mut spine = String::new();
+
+    for (idx, chapter) in chapters.iter().enumerate() {
+        manifest.push_str(&format!(
+            r#"<item id="slide-{}" href="text/{}" media-type="application/xhtml+xml"/>"#,
+            idx + 1,
+            chapter.file
+        ));
+        spine.push_str(&format!(r#"<itemref idref="slide-{}"/>"#, idx + 1));
+    }
+
+    for (idx, image) in images.iter().enumerate() {
+        manifest.push_str(&format!(
+            r#"<item id="image-{}" href="images/{}" media-type="{}"/>"#,
+            idx + 1,
+            image.file,
+            image.media_type
+        ));
+    }
+
+    format!(
+        r#"{}<package xmlns="http://www.idpf.org/2007/opf" version="3.0" unique-identifier="book-id" xml:lang="{}"><metadata xmlns:dc="http://purl.org/dc/elements/1.1/">{}</metadata><manifest>{}</manifest><spine>{}</spine></package>"#,
+        XML_HEADER,
+        xml_escape(&language),
+        metadata,
+        manifest,
+        spine
+    )
+}
+
+/// Navigation document listing every chapter
+fn navigation(document: &Document, chapters: &[Chapter]) -> String {
+    let entries: String = chapters
+        .iter()
+        .map(|chapter| {
+            format!(
+                r#"<li><a href="text/{}">{}</a></li>"#,
+                chapter.file,
+                xml_escape(&chapter.title)
+            )
+        })
+        .collect();
+
+    xhtml(
+        &document.language,
+        &document.title,
+        "style.css",
+        &format!(
+            r#"<nav epub:type="toc" id="toc"><h1>{}</h1><ol>{}</ol></nav>"#,
+            xml_escape(&document.title),
+            entries
+        ),
+    )
+}
+
+fn xhtml(language: &str, title: &str, stylesheet: &str, body: &str) -> String {
+    let language = xml_escape(&language_tag(language));
+    format!(
+        r#"{}<!DOCTYPE html><html xmlns="http://www.w3.org/1999/xhtml" xmlns:epub="http://www.idpf.org/2007/ops" lang="{}" xml:lang="{}"><head><meta charset="UTF-8"/><title>{}</title><link rel="stylesheet" type="text/css" href="{}"/></head><body>{}</body></html>"#,
+        XML_HEADER,
+        language,
+        language,
+        xml_escape(title),
+        stylesheet,
+        body
+    )
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or_default()
+}
+
+/// UTC date time of a unix timestamp, as required by `dcterms:modified`
+fn timestamp(secs: u64) -> String {
+    // Civil date from the days since 1970-01-01, see http://howardhinnant.github.io/date_algorithms.html
+    let days = (secs / 86400) as i64 + 719468;
+    let era = days.div_euclid(146097);
+    let day_of_era = days - era * 146097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 {
+        month_index + 3
+    } else {
+        month_index - 9
+    };
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+
+    let time = secs % 86400;
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+        year,
+        month,
+        day,
+        time / 3600,
+        time % 3600 / 60,
+        time % 60
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{document::Document, export::epub};
+    use std::io::{Cursor, Read};
+    use zip::{CompressionMethod, ZipArchive};
+
+    const OPF: &str = "http://www.idpf.org/2007/opf";
+    const DC: &str = "http://purl.org/dc/elements/1.1/";
+
+    #[test]
+    fn should_write_epub_package() {
+        let mut document = Document::parse(
+            "demo talk",
+            "EN",
+            &[
+                (
+                    "slides/a.md".into(),
+                    "# Hello [class=\"red\"]\n\n![remote](https://example.org/ferris.png)\n\n```rust\nfn main() {}\n```\n???\nSay *hello*"
+                        .into(),
+                ),
+                ("slides/b.md".into(), "Some <br> text".into()),
+            ],
+        );
+        document.author = Some("Ferris".into());
+
+        let mut output = Cursor::new(vec![]);
+        epub::write(&document, &mut output).unwrap();
+
+        let mut archive = ZipArchive::new(output).unwrap();
+        let mimetype = archive.by_index(0).unwrap();
+        assert_eq!(mimetype.name(), "mimetype");
+        assert_eq!(mimetype.compression(), CompressionMethod::Stored);
+        drop(mimetype);
+
+        let mut read = |name: &str| {
+            let mut content = String::new();
+            archive
+                .by_name(name)
+                .unwrap()
+                .read_to_string(&mut content)
+                .unwrap();
+            content
+        };
+
+        let container = read("META-INF/container.xml");
+        let package = read("OEBPS/content.opf");
+        let nav = read("OEBPS/nav.xhtml");
+        let chapter = read("OEBPS/text/slide-1.xhtml");
+        let second_chapter = read("OEBPS/text/slide-2.xhtml");
+
+        // Every part is well formed xml
+        let options = roxmltree::ParsingOptions {
+            allow_dtd: true,
+            ..roxmltree::ParsingOptions::default()
+        };
+        for part in [&container, &nav, &chapter, &second_chapter] {
+            roxmltree::Document::parse_with_options(part, options).unwrap();
+        }
+
+        let package = roxmltree::Document::parse(&package).unwrap();
+        let text = |namespace: &str, name: &str| {
+            package
+                .descendants()
+                .find(|node| node.has_tag_name((namespace, name)))
+                .and_then(|node| node.text())
+                .map(str::to_string)
+        };
+        assert_eq!(text(DC, "title"), Some("demo talk".into()));
+        assert_eq!(text(DC, "language"), Some("en".into()));
+        assert_eq!(text(DC, "creator"), Some("Ferris".into()));
+        assert_eq!(text(DC, "identifier"), Some("urn:unveil:demo-talk".into()));
+
+        let spine: Vec<&str> = package
+            .descendants()
+            .filter(|node| node.has_tag_name((OPF, "itemref")))
+            .filter_map(|node| node.attribute("idref"))
+            .collect();
+        assert_eq!(spine, vec!["slide-1", "slide-2"]);
+
+        assert!(nav.contains(r#"<li><a href="text/slide-1.xhtml">Hello</a></li>"#));
+        assert!(chapter.contains("<h1>Hello</h1>"));
+        assert!(chapter.contains("<em>remote</em>"));
+        assert!(chapter.contains(r#"<span style="color:"#));
+        assert!(chapter.contains(r#"<div class="notes"><p>Say <em>hello</em></p>"#));
+    }
+
+    #[test]
+    fn should_format_modification_date() {
+        assert_eq!(epub::timestamp(0), "1970-01-01T00:00:00Z");
+        assert_eq!(epub::timestamp(1_709_164_800), "2024-02-29T00:00:00Z");
+        assert_eq!(epub::timestamp(1_700_000_000), "2023-11-14T22:13:20Z");
+    }
+}
@@ -0,0 +1,309 @@
+use crate::{
+    assets::{HANDOUT_CSS, HIGHLIGHT_CSS, HIGHLIGHT_JS},
+    document::{Document, MD_CLASS_RE},
+    export::lookup_image,
+    html::HtmlBuilder,
+};
+use anyhow::Result;
+use base64::{engine::general_purpose::STANDARD, Engine};
+use horrorshow::{helper::doctype, html, prelude::*};
+use once_cell::sync::Lazy;
+use regex::Regex;
+use std::{fs, io::Write};
+
+static IMG_SRC_RE: Lazy<Regex> = Lazy::new(|| Regex::new(r#"<img src="([^"]*)""#).unwrap());
+
+/// Entry of the table of contents
+struct TocEntry {
+    anchor: String,
+    level: u32,
+    title: String,
+}
+
+/// Write the handout as a self contained html page, slides followed by their notes
+pub fn write_html<W: Write>(document: &Document, mut writer: W) -> Result<()> {
+    let toc = table_of_contents(document);
+    let slides: Vec<(String, String, Option<String>)> = document
+        .slides
+        .iter()
+        .enumerate()
+        .map(|(idx, slide)| {
+            let html = inline_images(&HtmlBuilder::render_slide(&slide.markdown));
+            let notes = slide
+                .notes_markdown
+                .as_deref()
+                .map(|notes| inline_images(&HtmlBuilder::render_slide(notes)));
+            (anchor(idx), html, notes)
+        })
+        .collect();
+
+    let has_code = slides.iter().any(|(_, html, notes)| {
+        html.contains("<code") || notes.iter().any(|notes| notes.contains("<code"))
+    });
+    let byline = byline(document);
+    let css = String::from_utf8_lossy(HANDOUT_CSS);
+    let highlight_css = String::from_utf8_lossy(HIGHLIGHT_CSS);
+    let highlight_js = String::from_utf8_lossy(HIGHLIGHT_JS);
+
+    let html = html! {
+        : doctype::HTML;
+        html(lang=document.language.to_lowercase()) {
+            head {
+                meta(charset="utf8");
+                meta(name="viewport", content="width=device-width, initial-scale=1");
+                title : &document.title;
+                style : Raw(&css);
+                |tmpl| {
+                    if has_code {
+                        tmpl << html! { style : Raw(&highlight_css) };
+                    }
+                }
+            }
+            body(class="unveil-handout") {
+                header {
+                    h1 : &document.title;
+                    @ if let Some(byline) = &byline {
+                        p : byline;
+                    }
+                }
+                nav(class="unveil-toc") {
+                    h2 : "Contents";
+                    ol {
+                        @ for entry in toc.iter() {
+                            li(class=format!("toc-level-{}", entry.level)) {
+                                a(href=format!("#{}", entry.anchor)) : &entry.title;
+                            }
+                        }
+                    }
+                }
+                main {
+                    @ for (anchor, html, notes) in slides.iter() {
+                        section(id=anchor) {
+                            article { : Raw(html) }
+                            @ if let Some(notes) = notes {
+                                aside(class="notes") {
+                                    h3 : "Notes";
+                                    : Raw(notes)
+                                }
+                            }
+                        }
+                    }
+                }
+                |tmpl| {
+                    if has_code {
+                        tmpl << html! {
+                            script : Raw(&highlight_js);
+                            script {
+                                : Raw("document.querySelectorAll('pre code').forEach(block => hljs.highlightBlock(block));")
+                            }
+                        };
+                    }
+                }
+            }
+        }
+    };
+
+    writer.write_all(format!("{}", html).as_bytes())?;
+    Ok(())
+}
+
+/// Markdown version of the handout, notes being rendered as quotes
+pub fn markdown(document: &Document) -> String {
+    let mut markdown = format!("# {}\n\n", document.title);
+
+    if let Some(byline) = byline(document) {
+        markdown.push_str(&format!("{}\n\n", byline));
+    }
+
+    markdown.push_str("## Contents\n\n");
+    let toc = table_of_contents(document);
+    let min_level = toc.iter().map(|entry| entry.level).min().unwrap_or(1);
+    for entry in toc.iter() {
+        markdown.push_str(&format!(
+            "{}- [{}](#{})\n",
+            "  ".repeat((entry.level - min_level) as usize),
+            entry.title,
+            entry.anchor
+        ));
+    }
+
+    for (idx, slide) in document.slides.iter().enumerate() {
+        markdown.push_str(&format!("\n---\n\n<a id=\"{}\"></a>\n\n", anchor(idx)));
+        for line in without_classes(slide.markdown.trim()).lines() {
+            markdown.push_str(line);
+            markdown.push('\n');
+        }
+
+        if let Some(notes) = &slide.notes_markdown {
+            markdown.push_str("\n> **Notes**\n>\n");
+            for line in without_classes(notes).lines() {
+                markdown.push_str(format!("> {}", line).trim_end());
+                markdown.push('\n');
+            }
+        }
+    }
+
+    markdown
+}
+
+/// Drop the `[class="..."]` markdown extension and the blanks it leaves behind
+fn without_classes(markdown: &str) -> String {
+    MD_CLASS_RE
+        .replace_all(markdown, "")
+        .lines()
+        .map(str::trim_end)
+        .collect::<Vec<&str>>()
+        .join("\n")
+}
+
+fn table_of_contents(document: &Document) -> Vec<TocEntry> {
+    document
+        .slides
+        .iter()
+        .enumerate()
+        .filter_map(|(idx, slide)| {
+            slide.heading().map(|(level, title)| TocEntry {
+                anchor: anchor(idx),
+                level,
+                title,
+            })
+        })
+        .collect()
+}
+
+fn anchor(idx: usize) -> String {
+    format!("slide-{}", idx + 1)
+}
+
+fn byline(document: &Document) -> Option<String> {
+    match (&document.author, &document.date) {
+        (Some(author), Some(date)) => Some(format!("{}, {}", author, date)),
+        (Some(value), None) | (None, Some(value)) => Some(value.clone()),
+        (None, None) => None,
+    }
+}
+
+/// Embed the local images so the handout is a single file
+fn inline_images(html: &str) -> String {
+    IMG_SRC_RE
+        .replace_all(html, |caps: &regex::Captures| {
+            let src = &caps[1];
+            let mime_type = match src
+                .rsplit('.')
+                .next()
+                .map(|ext| ext.to_lowercase())
+                .as_deref()
+            {
+                Some("png") => "image/png",
+                Some("jpg") | Some("jpeg") => "image/jpeg",
+                Some("gif") => "image/gif",
+                Some("webp") => "image/webp",
+                Some("svg") => "image/svg+xml",
+                _ => return caps[0].to_string(),
+            };
+
+            match lookup_image(src).and_then(|path| fs::read(path).ok()) {
+                Some(content) => format!(
+                    r#"<img src="data:{};base64,{}""#,
+                    mime_type,
+                    STANDARD.encode(content)
+                ),
+                None => caps[0].to_string(),
+            }
+        })
+        .to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        document::Document,
+        export::{self, handout, ExportFormat},
+    };
+
+    fn document() -> Document {
+        Document::parse(
+            "demo",
+            "EN",
+            &[
+                (
+                    "slides/a.md".into(),
+                    "# Intro [class=\"red\"]\n\nHello\n???\nWelcome *everyone*".into(),
+                ),
+                (
+                    "slides/b.md".into(),
+                    "## Code\n\n```rust\nfn main() {}\n```\n".into(),
+                ),
+            ],
+        )
+    }
+
+    #[test]
+    fn should_write_html_handout_with_toc_and_notes() {
+        let mut output = vec![];
+        handout::write_html(&document(), &mut output).unwrap();
+        let html = String::from_utf8(output).unwrap();
+
+        assert!(html.contains(r##"<li class="toc-level-1"><a href="#slide-1">Intro</a></li>"##));
+        assert!(html.contains(r##"<li class="toc-level-2"><a href="#slide-2">Code</a></li>"##));
+        assert!(html.contains(r#"<section id="slide-1"><article><h1 class="red">Intro </h1>"#));
+        assert!(
+            html.contains(r#"<aside class="notes"><h3>Notes</h3><p>Welcome <em>everyone</em></p>"#)
+        );
+        assert!(html.contains("hljs.highlightBlock"));
+    }
+
+    #[test]
+    fn should_write_markdown_handout() {
+        let markdown = handout::markdown(&document());
+
+        assert_eq!(
+            markdown,
+            r#"# demo
+
+## Contents
+
+- [Intro](#slide-1)
+  - [Code](#slide-2)
+
+---
+
+<a id="slide-1"></a>
+
+# Intro
+
+Hello
+
+> **Notes**
+>
+> Welcome *everyone*
+
+---
+
+<a id="slide-2"></a>
+
+## Code
+
+```rust
+fn main() {}
+```
+"#
+        );
+    }
+
+    #[test]
+    fn should_not_overwrite_handout_with_its_markdown() {
+        let dir = tempfile::tempdir().unwrap();
+        let output = dir.path().join("handout.md");
+
+        let err =
+            export::export(&document(), ExportFormat::Handout, (1280, 720), &output).unwrap_err();
+
+        assert!(err.to_string().contains("use a `.html` file name"));
+        assert!(!output.exists());
+
+        let output = dir.path().join("handout.html");
+        export::export(&document(), ExportFormat::Handout, (1280, 720), &output).unwrap();
+        assert!(output.exists() && dir.path().join("handout.md").exists());
+    }
+}
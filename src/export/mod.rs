@@ -0,0 +1,202 @@
+use crate::document::{Document, Row, Span};
+use anyhow::{anyhow, Error, Result};
+use std::{
+    fs,
+    fs::File,
+    io::BufWriter,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+mod epub;
+mod handout;
+mod odp;
+mod pptx;
+
+/// Formats supported by `unveil export`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExportFormat {
+    Pptx,
+    Odp,
+    /// Single html page, and its markdown version, with the slides followed by their notes
+    Handout,
+    /// EPUB 3 book with a chapter per slide
+    Epub,
+}
+
+impl FromStr for ExportFormat {
+    type Err = Error;
+
+    fn from_str(format: &str) -> Result<Self> {
+        match format {
+            "pptx" => Ok(ExportFormat::Pptx),
+            "odp" => Ok(ExportFormat::Odp),
+            "handout" => Ok(ExportFormat::Handout),
+            "epub" => Ok(ExportFormat::Epub),
+            other => Err(anyhow!("Unknown export format {}", other)),
+        }
+    }
+}
+
+impl ExportFormat {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ExportFormat::Pptx => "pptx",
+            ExportFormat::Odp => "odp",
+            ExportFormat::Handout => "html",
+            ExportFormat::Epub => "epub",
+        }
+    }
+}
+
+/// Page width and height used to size exported slides, in css pixels
+pub type PageSize = (u32, u32);
+
+/// Write `document` to the `output` file in the given format
+pub fn export(
+    document: &Document,
+    format: ExportFormat,
+    page_size: PageSize,
+    output: &Path,
+) -> Result<()> {
+    // The handout markdown is written next to the html one, with the `md` extension
+    if format == ExportFormat::Handout
+        && output
+            .extension()
+            .is_some_and(|extension| extension == "md")
+    {
+        return Err(anyhow!(
+            "The handout output {} would be overwritten by its markdown version, use a `.html` file name",
+            output.display()
+        ));
+    }
+
+    let file = File::create(output)
+        .map_err(|err| anyhow!("Could not create {} : {}", output.display(), err))?;
+    let writer = BufWriter::new(file);
+
+    match format {
+        ExportFormat::Pptx => pptx::write(document, page_size, writer),
+        ExportFormat::Odp => odp::write(document, page_size, writer),
+        ExportFormat::Epub => epub::write(document, writer),
+        ExportFormat::Handout => {
+            handout::write_html(document, writer)?;
+            let markdown = output.with_extension("md");
+            fs::write(&markdown, handout::markdown(document))
+                .map_err(|err| anyhow!("Could not create {} : {}", markdown.display(), err))
+        }
+    }
+}
+
+/// A local image in a format office suites can embed
+pub(crate) struct LocalImage {
+    pub path: PathBuf,
+    /// Normalized extension, also used as the image subtype of its mime type
+    pub extension: &'static str,
+    pub width: u32,
+    pub height: u32,
+}
+
+/// Find a local image the same way images are resolved when building the html
+pub(crate) fn lookup_image(src: &str) -> Option<PathBuf> {
+    image_candidates(src)
+        .into_iter()
+        .find(|path| path.is_file())
+}
+
+/// Paths a local image is looked up from, in order, none for remote urls
+pub(crate) fn image_candidates(src: &str) -> Vec<PathBuf> {
+    if src.contains("://") || src.starts_with("//") || src.starts_with("data:") {
+        return vec![];
+    }
+
+    let relative = Path::new(src.trim_start_matches('/'));
+    [PathBuf::from("."), PathBuf::from("public")]
+        .iter()
+        .map(|dir| dir.join(relative))
+        .collect()
+}
+
+/// Find a local image in a format every office suite can display
+pub(crate) fn local_image(src: &str) -> Option<LocalImage> {
+    let path = lookup_image(src)?;
+
+    let extension = match path
+        .extension()
+        .and_then(|extension| extension.to_str())
+        .map(|extension| extension.to_lowercase())
+        .as_deref()
+    {
+        Some("png") => "png",
+        Some("jpg") | Some("jpeg") => "jpeg",
+        Some("gif") => "gif",
+        _ => return None,
+    };
+
+    let (width, height) = image::image_dimensions(&path).ok()?;
+
+    Some(LocalImage {
+        path,
+        extension,
+        width,
+        height,
+    })
+}
+
+/// Office suites expect an IETF language tag such as `en-US`, unveil.toml uses `EN`
+pub(crate) fn language_tag(language: &str) -> String {
+    if language.contains('-') {
+        language.to_string()
+    } else {
+        language.to_lowercase()
+    }
+}
+
+/// Rough estimate of the wrapped lines of a paragraph, glyphs being on
+/// average half as wide as high. Sizes can be in any unit.
+pub(crate) fn text_lines(spans: &[Span], font_size: i64, width: i64) -> i64 {
+    let chars_per_line = (width / (font_size / 2)).max(1);
+    let chars: i64 = spans
+        .iter()
+        .map(|span| span.text.chars().count() as i64)
+        .sum();
+    chars / chars_per_line + 1
+}
+
+/// Tables are exported as one line of text per row, header cells in bold
+pub(crate) fn table_lines(header: &Row, rows: &[Row]) -> Vec<Vec<Span>> {
+    std::iter::once((header, true))
+        .chain(rows.iter().map(|row| (row, false)))
+        .map(|(row, bold)| {
+            let mut line = vec![];
+            for (idx, cell) in row.iter().enumerate() {
+                if idx > 0 {
+                    line.push(Span {
+                        text: " | ".to_string(),
+                        ..Span::default()
+                    });
+                }
+                line.extend(cell.iter().map(|span| Span {
+                    bold: span.bold || bold,
+                    ..span.clone()
+                }));
+            }
+            line
+        })
+        .collect()
+}
+
+pub(crate) fn xml_escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for char in text.chars() {
+        match char {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            char => escaped.push(char),
+        }
+    }
+    escaped
+}
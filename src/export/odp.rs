@@ -0,0 +1,640 @@
+use crate::{
+    document::{Block, Document, DocumentSlide, ListItem, Span},
+    export,
+    export::{language_tag, local_image, table_lines, xml_escape, PageSize},
+};
+use anyhow::Result;
+use std::{
+    fs,
+    io::{Seek, Write},
+};
+use zip::{write::SimpleFileOptions, CompressionMethod, ZipWriter};
+
+// Lengths are expressed in points, a css pixel being 0.75pt
+const MARGIN: i64 = 36;
+const TITLE_SIZE: i64 = 32;
+const HEADING_SIZE: i64 = 24;
+const TEXT_SIZE: i64 = 18;
+const CODE_SIZE: i64 = 12;
+const CODE_FONT: &str = "Courier New";
+
+const MIME_TYPE: &str = "application/vnd.oasis.opendocument.presentation";
+const XML_HEADER: &str = r#"<?xml version="1.0" encoding="UTF-8"?>"#;
+const NAMESPACES: &str = r#"xmlns:office="urn:oasis:names:tc:opendocument:xmlns:office:1.0" xmlns:style="urn:oasis:names:tc:opendocument:xmlns:style:1.0" xmlns:text="urn:oasis:names:tc:opendocument:xmlns:text:1.0" xmlns:draw="urn:oasis:names:tc:opendocument:xmlns:drawing:1.0" xmlns:fo="urn:oasis:names:tc:opendocument:xmlns:xsl-fo-compatible:1.0" xmlns:svg="urn:oasis:names:tc:opendocument:xmlns:svg-compatible:1.0" xmlns:presentation="urn:oasis:names:tc:opendocument:xmlns:presentation:1.0" xmlns:xlink="http://www.w3.org/1999/xlink" xmlns:dc="http://purl.org/dc/elements/1.1/" xmlns:meta="urn:oasis:names:tc:opendocument:xmlns:meta:1.0" office:version="1.3""#;
+
+/// Write the document as an OpenDocument presentation
+pub fn write<W: Write + Seek>(document: &Document, page_size: PageSize, writer: W) -> Result<()> {
+    let (width, height) = page_size;
+    let page_width = width as i64 * 3 / 4;
+    let page_height = height as i64 * 3 / 4;
+
+    let mut odp = Odp {
+        zip: ZipWriter::new(writer),
+        options: SimpleFileOptions::default().compression_method(CompressionMethod::Deflated),
+        pictures: vec![],
+        page_width,
+        page_height,
+    };
+
+    // The mime type must be the first entry, uncompressed
+    odp.zip.start_file(
+        "mimetype",
+        SimpleFileOptions::default().compression_method(CompressionMethod::Stored),
+    )?;
+    odp.zip.write_all(MIME_TYPE.as_bytes())?;
+
+    let mut pages = String::new();
+    for (idx, slide) in document.slides.iter().enumerate() {
+        pages.push_str(&odp.page(idx + 1, slide)?);
+    }
+
+    let content = format!(
+        r#"{}<office:document-content {}><office:automatic-styles>{}</office:automatic-styles><office:body><office:presentation>{}</office:presentation></office:body></office:document-content>"#,
+        XML_HEADER,
+        NAMESPACES,
+        automatic_styles(),
+        pages
+    );
+
+    odp.write_part("content.xml", &content)?;
+    odp.write_part("styles.xml", &odp.styles(&document.language))?;
+    odp.write_part("meta.xml", &meta(document))?;
+    odp.write_part("META-INF/manifest.xml", &odp.manifest())?;
+    odp.zip.finish()?;
+    Ok(())
+}
+
+struct Odp<W: Write + Seek> {
+    zip: ZipWriter<W>,
+    options: SimpleFileOptions,
+    /// Path and mime type of the embedded images
+    pictures: Vec<(String, String)>,
+    page_width: i64,
+    page_height: i64,
+}
+
+impl<W: Write + Seek> Odp<W> {
+    fn write_part(&mut self, name: &str, content: &str) -> Result<()> {
+        self.zip.start_file(name, self.options)?;
+        self.zip.write_all(content.as_bytes())?;
+        Ok(())
+    }
+
+    fn page(&mut self, number: usize, slide: &DocumentSlide) -> Result<String> {
+        let mut frames = Frames {
+            xml: String::new(),
+            x: MARGIN,
+            y: MARGIN,
+            width: self.page_width - 2 * MARGIN,
+        };
+
+        // Consecutive text blocks share the same frame
+        let mut paragraphs = String::new();
+        let mut lines = 0;
+        let mut blocks = slide.blocks.iter().peekable();
+
+        // A leading heading is the slide title
+        if let Some(Block::Heading { content, .. }) = blocks.peek() {
+            let title = paragraph("PTitle", content);
+            let lines = frames.lines(content, TITLE_SIZE);
+            frames.push_text("grText", &title, lines * line_height(TITLE_SIZE));
+            blocks.next();
+        }
+
+        for block in blocks {
+            match block {
+                Block::Code { code, .. } => {
+                    frames.flush(&mut paragraphs, &mut lines);
+                    let code = code.trim_end_matches('\n');
+                    let code_paragraphs = code
+                        .lines()
+                        .map(|line| {
+                            let span = Span {
+                                text: line.to_string(),
+                                ..Span::default()
+                            };
+                            paragraph("PCode", &[span])
+                        })
+                        .collect::<String>();
+                    let height = line_height(CODE_SIZE) * code.lines().count().max(1) as i64;
+                    frames.push_text("grCode", &code_paragraphs, height + 12);
+                }
+                Block::Image { src, alt } => {
+                    frames.flush(&mut paragraphs, &mut lines);
+                    if !self.push_image(&mut frames, src, alt)? {
+                        let span = Span {
+                            text: alt.clone(),
+                            italic: true,
+                            ..Span::default()
+                        };
+                        paragraphs.push_str(&paragraph("PText", &[span]));
+                        lines += 1;
+                    }
+                }
+                Block::Heading { content, .. } => {
+                    paragraphs.push_str(&paragraph("PHeading", content));
+                    lines += frames.lines(content, HEADING_SIZE);
+                }
+                Block::Paragraph(content) => {
+                    paragraphs.push_str(&paragraph("PText", content));
+                    lines += frames.lines(content, TEXT_SIZE);
+                }
+                Block::Quote(content) => {
+                    paragraphs.push_str(&paragraph("PQuote", content));
+                    lines += frames.lines(content, TEXT_SIZE);
+                }
+                Block::List { ordered, items } => {
+                    paragraphs.push_str(&list(*ordered, items));
+                    lines += items
+                        .iter()
+                        .map(|item| frames.lines(&item.content, TEXT_SIZE))
+                        .sum::<i64>();
+                }
+                Block::Table { header, rows } => {
+                    for line in table_lines(header, rows) {
+                        paragraphs.push_str(&paragraph("PText", &line));
+                        lines += frames.lines(&line, TEXT_SIZE);
+                    }
+                }
+                Block::Rule => {}
+            }
+        }
+
+        frames.flush(&mut paragraphs, &mut lines);
+
+        Ok(format!(
+            r#"<draw:page draw:name="page{}" draw:style-name="dp1" draw:master-page-name="Default">{}{}</draw:page>"#,
+            number,
+            frames.xml,
+            self.notes(number, slide)
+        ))
+    }
+
+    /// Embed a local image, `false` when it can not be embedded
+    fn push_image(&mut self, frames: &mut Frames, src: &str, alt: &str) -> Result<bool> {
+        let image = match local_image(src) {
+            Some(image) => image,
+            None => return Ok(false),
+        };
+
+        let path = format!(
+            "Pictures/image{}.{}",
+            self.pictures.len() + 1,
+            image.extension
+        );
+        self.zip.start_file(&path, self.options)?;
+        self.zip.write_all(&fs::read(&image.path)?)?;
+        self.pictures
+            .push((path.clone(), format!("image/{}", image.extension)));
+
+        // Images are displayed at 96 dpi, scaled down to fit the slide
+        let mut width = image.width as i64 * 3 / 4;
+        let mut height = image.height as i64 * 3 / 4;
+        let available_height = (self.page_height - frames.y - MARGIN).max(MARGIN);
+
+        if width > frames.width {
+            height = height * frames.width / width;
+            width = frames.width;
+        }
+
+        if height > available_height {
+            width = width * available_height / height;
+            height = available_height;
+        }
+
+        frames.xml.push_str(&format!(
+            r#"<draw:frame draw:style-name="grText" svg:x="{}pt" svg:y="{}pt" svg:width="{}pt" svg:height="{}pt"><draw:image xlink:href="{}" xlink:type="simple" xlink:show="embed" xlink:actuate="onLoad"/><svg:desc>{}</svg:desc></draw:frame>"#,
+            frames.x + (frames.width - width) / 2,
+            frames.y,
+            width,
+            height,
+            path,
+            xml_escape(alt)
+        ));
+        frames.y += height + 8;
+
+        Ok(true)
+    }
+
+    fn notes(&self, number: usize, slide: &DocumentSlide) -> String {
+        let paragraphs = slide
+            .notes
+            .iter()
+            .flat_map(|block| {
+                block
+                    .plain_text()
+                    .lines()
+                    .map(|line| {
+                        let span = Span {
+                            text: line.to_string(),
+                            ..Span::default()
+                        };
+                        paragraph("PNotes", &[span])
+                    })
+                    .collect::<Vec<String>>()
+            })
+            .collect::<String>();
+
+        format!(
+            r#"<presentation:notes draw:style-name="dp1"><draw:page-thumbnail presentation:class="page" draw:page-number="{}" svg:x="108pt" svg:y="54pt" svg:width="379pt" svg:height="{}pt"/><draw:frame presentation:class="notes" svg:x="54pt" svg:y="{}pt" svg:width="487pt" svg:height="340pt"><draw:text-box>{}</draw:text-box></draw:frame></presentation:notes>"#,
+            number,
+            379 * self.page_height / self.page_width,
+            84 + 379 * self.page_height / self.page_width,
+            paragraphs
+        )
+    }
+
+    fn styles(&self, language: &str) -> String {
+        let language = language_tag(language);
+        let (language, country) = language.split_once('-').unwrap_or((&language, ""));
+        let country = if country.is_empty() {
+            String::new()
+        } else {
+            format!(r#" fo:country="{}""#, xml_escape(country))
+        };
+
+        format!(
+            r##"{}<office:document-styles {}><office:styles><style:default-style style:family="graphic"><style:text-properties fo:language="{}"{} fo:font-size="{}pt"/></style:default-style></office:styles><office:automatic-styles><style:page-layout style:name="PM1"><style:page-layout-properties fo:margin-top="0pt" fo:margin-bottom="0pt" fo:margin-left="0pt" fo:margin-right="0pt" fo:page-width="{}pt" fo:page-height="{}pt" style:print-orientation="landscape"/></style:page-layout><style:style style:name="dp1" style:family="drawing-page"><style:drawing-page-properties draw:fill="solid" draw:fill-color="#ffffff"/></style:style></office:automatic-styles><office:master-styles><style:master-page style:name="Default" style:page-layout-name="PM1" draw:style-name="dp1"/></office:master-styles></office:document-styles>"##,
+            XML_HEADER,
+            NAMESPACES,
+            xml_escape(language),
+            country,
+            TEXT_SIZE,
+            self.page_width,
+            self.page_height
+        )
+    }
+
+    fn manifest(&self) -> String {
+        let mut entries = format!(
+            r#"<manifest:file-entry manifest:full-path="/" manifest:version="1.3" manifest:media-type="{}"/>"#,
+            MIME_TYPE
+        );
+
+        for part in ["content.xml", "styles.xml", "meta.xml"] {
+            entries.push_str(&format!(
+                r#"<manifest:file-entry manifest:full-path="{}" manifest:media-type="text/xml"/>"#,
+                part
+            ));
+        }
+
+        for (path, mime_type) in self.pictures.iter() {
+            entries.push_str(&format!(
+                r#"<manifest:file-entry manifest:full-path="{}" manifest:media-type="{}"/>"#,
+                path, mime_type
+            ));
+        }
+
+        format!(
+            r#"{}<manifest:manifest xmlns:manifest="urn:oasis:names:tc:opendocument:xmlns:manifest:1.0" manifest:version="1.3">{}</manifest:manifest>"#,
+            XML_HEADER, entries
+        )
+    }
+}
+
+/// Frames of a page, stacked from top to bottom
+struct Frames {
+    xml: String,
+    x: i64,
+    y: i64,
+    width: i64,
+}
+
+impl Frames {
+    fn lines(&self, spans: &[Span], size: i64) -> i64 {
+        export::text_lines(spans, size, self.width)
+    }
+
+    fn push_text(&mut self, style: &str, paragraphs: &str, height: i64) {
+        self.xml.push_str(&format!(
+            r#"<draw:frame draw:style-name="{}" svg:x="{}pt" svg:y="{}pt" svg:width="{}pt" svg:height="{}pt"><draw:text-box>{}</draw:text-box></draw:frame>"#,
+            style, self.x, self.y, self.width, height, paragraphs
+        ));
+        self.y += height + 8;
+    }
+
+    fn flush(&mut self, paragraphs: &mut String, lines: &mut i64) {
+        if !paragraphs.is_empty() {
+            self.push_text(
+                "grText",
+                paragraphs,
+                *lines * line_height(TEXT_SIZE) * 3 / 2,
+            );
+            paragraphs.clear();
+            *lines = 0;
+        }
+    }
+}
+
+fn line_height(size: i64) -> i64 {
+    size * 6 / 5
+}
+
+fn paragraph(style: &str, spans: &[Span]) -> String {
+    let content = spans.iter().map(span).collect::<String>();
+    format!(
+        r#"<text:p text:style-name="{}">{}</text:p>"#,
+        style, content
+    )
+}
+
+fn span(span: &Span) -> String {
+    let mut style = String::from("T");
+    if span.bold {
+        style.push('b');
+    }
+    if span.italic {
+        style.push('i');
+    }
+    if span.code {
+        style.push('c');
+    }
+
+    let text = if style.len() > 1 {
+        format!(
+            r#"<text:span text:style-name="{}">{}</text:span>"#,
+            style,
+            text(&span.text)
+        )
+    } else {
+        text(&span.text)
+    };
+
+    match &span.link {
+        Some(link) => format!(
+            r#"<text:a xlink:type="simple" xlink:href="{}">{}</text:a>"#,
+            xml_escape(link),
+            text
+        ),
+        None => text,
+    }
+}
+
+/// Escape text, keeping the spaces and tabs that odf would otherwise collapse
+fn text(text: &str) -> String {
+    let mut result = String::new();
+    let mut spaces = 0;
+
+    let flush_spaces = |result: &mut String, spaces: &mut usize| {
+        match *spaces {
+            0 => {}
+            1 => result.push(' '),
+            count => result.push_str(&format!(r#" <text:s text:c="{}"/>"#, count - 1)),
+        }
+        *spaces = 0;
+    };
+
+    for char in text.chars() {
+        match char {
+            ' ' => spaces += 1,
+            '\t' => {
+                flush_spaces(&mut result, &mut spaces);
+                result.push_str("<text:tab/>");
+            }
+            char => {
+                flush_spaces(&mut result, &mut spaces);
+                result.push_str(&xml_escape(&char.to_string()));
+            }
+        }
+    }
+
+    flush_spaces(&mut result, &mut spaces);
+    result
+}
+
+/// Rebuild the nested lists from the flattened list items
+fn list(ordered: bool, items: &[ListItem]) -> String {
+    let style = if ordered { "LNumber" } else { "LBullet" };
+    let mut xml = format!(r#"<text:list text:style-name="{}">"#, style);
+    let mut depth = 0;
+
+    for (idx, item) in items.iter().enumerate() {
+        let level = item.level.min(depth + 1);
+
+        if idx > 0 {
+            if level > depth {
+                xml.push_str("<text:list>");
+            } else {
+                xml.push_str("</text:list-item>");
+                for _ in level..depth {
+                    xml.push_str("</text:list></text:list-item>");
+                }
+            }
+        }
+
+        depth = level;
+        xml.push_str("<text:list-item>");
+        xml.push_str(&paragraph("PText", &item.content));
+    }
+
+    if !items.is_empty() {
+        xml.push_str("</text:list-item>");
+    }
+
+    for _ in 0..depth {
+        xml.push_str("</text:list></text:list-item>");
+    }
+
+    xml.push_str("</text:list>");
+    xml
+}
+
+fn automatic_styles() -> String {
+    let mut styles = format!(
+        r##"<style:style style:name="grText" style:family="graphic"><style:graphic-properties draw:stroke="none" draw:fill="none" draw:auto-grow-height="true" fo:min-height="0pt"/></style:style><style:style style:name="grCode" style:family="graphic"><style:graphic-properties draw:stroke="none" draw:fill="solid" draw:fill-color="#f2f2f2" draw:auto-grow-height="true" fo:min-height="0pt" fo:padding="6pt"/></style:style><style:style style:name="PTitle" style:family="paragraph"><style:text-properties fo:font-size="{}pt" fo:font-weight="bold"/></style:style><style:style style:name="PHeading" style:family="paragraph"><style:paragraph-properties fo:margin-top="6pt"/><style:text-properties fo:font-size="{}pt" fo:font-weight="bold"/></style:style><style:style style:name="PText" style:family="paragraph"><style:paragraph-properties fo:margin-top="6pt"/><style:text-properties fo:font-size="{}pt"/></style:style><style:style style:name="PQuote" style:family="paragraph"><style:paragraph-properties fo:margin-left="36pt" fo:margin-top="6pt"/><style:text-properties fo:font-size="{}pt" fo:font-style="italic"/></style:style><style:style style:name="PCode" style:family="paragraph"><style:text-properties fo:font-family="'{}'" style:font-family-generic="modern" fo:font-size="{}pt"/></style:style><style:style style:name="PNotes" style:family="paragraph"><style:text-properties fo:font-size="12pt"/></style:style>"##,
+        TITLE_SIZE, HEADING_SIZE, TEXT_SIZE, TEXT_SIZE, CODE_FONT, CODE_SIZE
+    );
+
+    // Every combination of bold, italic and inline code
+    for style in ["b", "i", "c", "bi", "bc", "ic", "bic"] {
+        let mut properties = String::new();
+        if style.contains('b') {
+            properties.push_str(r#" fo:font-weight="bold""#);
+        }
+        if style.contains('i') {
+            properties.push_str(r#" fo:font-style="italic""#);
+        }
+        if style.contains('c') {
+            properties.push_str(&format!(
+                r#" fo:font-family="'{}'" style:font-family-generic="modern""#,
+                CODE_FONT
+            ));
+        }
+
+        styles.push_str(&format!(
+            r#"<style:style style:name="T{}" style:family="text"><style:text-properties{}/></style:style>"#,
+            style, properties
+        ));
+    }
+
+    for (name, ordered) in [("LBullet", false), ("LNumber", true)] {
+        styles.push_str(&format!(r#"<text:list-style style:name="{}">"#, name));
+        for level in 1..=5 {
+            let indent = format!(
+                r#"<style:list-level-properties text:space-before="{}pt" text:min-label-width="18pt"/>"#,
+                (level - 1) * 24
+            );
+            if ordered {
+                styles.push_str(&format!(
+                    r#"<text:list-level-style-number text:level="{}" style:num-format="1" style:num-suffix=".">{}</text:list-level-style-number>"#,
+                    level, indent
+                ));
+            } else {
+                styles.push_str(&format!(
+                    r#"<text:list-level-style-bullet text:level="{}" text:bullet-char="•">{}</text:list-level-style-bullet>"#,
+                    level, indent
+                ));
+            }
+        }
+        styles.push_str("</text:list-style>");
+    }
+
+    styles
+}
+
+fn meta(document: &Document) -> String {
+    let mut meta = format!(
+        "<meta:generator>Unveil</meta:generator><dc:title>{}</dc:title><dc:language>{}</dc:language>",
+        xml_escape(&document.title),
+        xml_escape(&language_tag(&document.language))
+    );
+
+    if let Some(author) = &document.author {
+        meta.push_str(&format!(
+            "<meta:initial-creator>{}</meta:initial-creator>",
+            xml_escape(author)
+        ));
+    }
+
+    if let Some(date) = &document.date {
+        meta.push_str(&format!("<dc:date>{}</dc:date>", xml_escape(date)));
+    }
+
+    format!(
+        r#"{}<office:document-meta {}><office:meta>{}</office:meta></office:document-meta>"#,
+        XML_HEADER, NAMESPACES, meta
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{document::Document, export::odp};
+    use std::io::{Cursor, Read};
+    use zip::{CompressionMethod, ZipArchive};
+
+    const OFFICE: &str = "urn:oasis:names:tc:opendocument:xmlns:office:1.0";
+    const DRAW: &str = "urn:oasis:names:tc:opendocument:xmlns:drawing:1.0";
+    const TEXT: &str = "urn:oasis:names:tc:opendocument:xmlns:text:1.0";
+    const PRESENTATION: &str = "urn:oasis:names:tc:opendocument:xmlns:presentation:1.0";
+
+    fn text_of(node: roxmltree::Node) -> String {
+        node.descendants()
+            .filter(|node| node.is_text())
+            .filter_map(|node| node.text())
+            .collect()
+    }
+
+    #[test]
+    fn should_write_valid_opendocument_package() {
+        let document = Document::parse(
+            "demo",
+            "en-GB",
+            &[
+                (
+                    "slides/a.md".into(),
+                    "# Hello\n\n- one\n    - nested\n- two\n\n```rust\nfn main() {\n    run();\n}\n```\n???\nSay hello"
+                        .into(),
+                ),
+                ("slides/b.md".into(), "Some *text*".into()),
+            ],
+        );
+
+        let mut output = Cursor::new(vec![]);
+        odp::write(&document, (1280, 720), &mut output).unwrap();
+
+        let mut archive = ZipArchive::new(output).unwrap();
+        let mimetype = archive.by_index(0).unwrap();
+        assert_eq!(mimetype.name(), "mimetype");
+        assert_eq!(mimetype.compression(), CompressionMethod::Stored);
+        drop(mimetype);
+
+        let mut parts = vec![];
+        for name in [
+            "content.xml",
+            "styles.xml",
+            "meta.xml",
+            "META-INF/manifest.xml",
+        ] {
+            let mut content = String::new();
+            archive
+                .by_name(name)
+                .unwrap()
+                .read_to_string(&mut content)
+                .unwrap();
+            parts.push(content);
+        }
+
+        // Every part is well formed xml
+        let documents: Vec<roxmltree::Document> = parts
+            .iter()
+            .map(|part| roxmltree::Document::parse(part).unwrap())
+            .collect();
+
+        let content = documents[0].root_element();
+        assert!(content.has_tag_name((OFFICE, "document-content")));
+
+        let pages: Vec<roxmltree::Node> = content
+            .descendants()
+            .filter(|node| node.has_tag_name((DRAW, "page")))
+            .collect();
+        assert_eq!(pages.len(), 2);
+
+        // Frames are direct children of the page and hold a text box
+        let texts: Vec<String> = pages[0]
+            .children()
+            .filter(|node| node.has_tag_name((DRAW, "frame")))
+            .map(|frame| {
+                let text_box = frame.first_element_child().unwrap();
+                assert!(text_box.has_tag_name((DRAW, "text-box")));
+                text_box
+                    .descendants()
+                    .filter(|node| node.has_tag_name((TEXT, "p")))
+                    .map(text_of)
+                    .collect::<Vec<String>>()
+                    .join("\n")
+            })
+            .collect();
+        assert_eq!(
+            texts,
+            vec!["Hello", "one\nnested\ntwo", "fn main() {\n run();\n}"]
+        );
+
+        // Nested list items are inside their parent item
+        let nested = pages[0]
+            .descendants()
+            .find(|node| {
+                node.has_tag_name((TEXT, "list"))
+                    && node.parent().unwrap().has_tag_name((TEXT, "list-item"))
+            })
+            .unwrap();
+        assert_eq!(text_of(nested), "nested");
+
+        // Indentation of the code is kept
+        assert!(parts[0]
+            .contains(r#"<text:p text:style-name="PCode"> <text:s text:c="3"/>run();</text:p>"#));
+
+        let notes = pages[0]
+            .children()
+            .find(|node| node.has_tag_name((PRESENTATION, "notes")))
+            .unwrap();
+        assert_eq!(text_of(notes), "Say hello");
+
+        let styles = documents[1].root_element();
+        assert!(styles.descendants().any(|node| node.attribute((
+            "urn:oasis:names:tc:opendocument:xmlns:xsl-fo-compatible:1.0",
+            "country"
+        )) == Some("GB")));
+
+        assert!(parts[3].contains(r#"manifest:full-path="content.xml""#));
+    }
+}
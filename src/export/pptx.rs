@@ -0,0 +1,742 @@
+use crate::{
+    document::{Block, Document, DocumentSlide, Span},
+    export,
+    export::{language_tag, local_image, table_lines, xml_escape, PageSize},
+};
+use anyhow::Result;
+use std::{
+    fs,
+    io::{Seek, Write},
+};
+use zip::{write::SimpleFileOptions, CompressionMethod, ZipWriter};
+
+// Office Open XML lengths are expressed in EMU
+const EMU_PER_INCH: i64 = 914_400;
+const EMU_PER_POINT: i64 = 12_700;
+const SLIDE_WIDTH: i64 = 12_192_000;
+const MARGIN: i64 = EMU_PER_INCH / 2;
+
+const TITLE_SIZE: i64 = 36;
+const TEXT_SIZE: i64 = 20;
+const CODE_SIZE: i64 = 14;
+const CODE_FONT: &str = "Courier New";
+
+const XML_HEADER: &str = r#"<?xml version="1.0" encoding="UTF-8" standalone="yes"?>"#;
+const NAMESPACES: &str = r#"xmlns:a="http://schemas.openxmlformats.org/drawingml/2006/main" xmlns:r="http://schemas.openxmlformats.org/officeDocument/2006/relationships" xmlns:p="http://schemas.openxmlformats.org/presentationml/2006/main""#;
+const RELATIONSHIP: &str = "http://schemas.openxmlformats.org/officeDocument/2006/relationships";
+
+const GROUP_SHAPE: &str = r#"<p:nvGrpSpPr><p:cNvPr id="1" name=""/><p:cNvGrpSpPr/><p:nvPr/></p:nvGrpSpPr><p:grpSpPr><a:xfrm><a:off x="0" y="0"/><a:ext cx="0" cy="0"/><a:chOff x="0" y="0"/><a:chExt cx="0" cy="0"/></a:xfrm></p:grpSpPr>"#;
+const COLOR_MAP: &str = r#"<p:clrMap bg1="lt1" tx1="dk1" bg2="lt2" tx2="dk2" accent1="accent1" accent2="accent2" accent3="accent3" accent4="accent4" accent5="accent5" accent6="accent6" hlink="hlink" folHlink="folHlink"/>"#;
+const BACKGROUND: &str = r#"<p:bg><p:bgRef idx="1001"><a:schemeClr val="bg1"/></p:bgRef></p:bg>"#;
+
+const THEME: &str = r#"<a:theme xmlns:a="http://schemas.openxmlformats.org/drawingml/2006/main" name="Unveil"><a:themeElements><a:clrScheme name="Unveil"><a:dk1><a:sysClr val="windowText" lastClr="000000"/></a:dk1><a:lt1><a:sysClr val="window" lastClr="FFFFFF"/></a:lt1><a:dk2><a:srgbClr val="44546A"/></a:dk2><a:lt2><a:srgbClr val="E7E6E6"/></a:lt2><a:accent1><a:srgbClr val="4472C4"/></a:accent1><a:accent2><a:srgbClr val="ED7D31"/></a:accent2><a:accent3><a:srgbClr val="A5A5A5"/></a:accent3><a:accent4><a:srgbClr val="FFC000"/></a:accent4><a:accent5><a:srgbClr val="5B9BD5"/></a:accent5><a:accent6><a:srgbClr val="70AD47"/></a:accent6><a:hlink><a:srgbClr val="1DA1F2"/></a:hlink><a:folHlink><a:srgbClr val="954F72"/></a:folHlink></a:clrScheme><a:fontScheme name="Unveil"><a:majorFont><a:latin typeface="Calibri Light"/><a:ea typeface=""/><a:cs typeface=""/></a:majorFont><a:minorFont><a:latin typeface="Calibri"/><a:ea typeface=""/><a:cs typeface=""/></a:minorFont></a:fontScheme><a:fmtScheme name="Unveil"><a:fillStyleLst><a:solidFill><a:schemeClr val="phClr"/></a:solidFill><a:solidFill><a:schemeClr val="phClr"/></a:solidFill><a:solidFill><a:schemeClr val="phClr"/></a:solidFill></a:fillStyleLst><a:lnStyleLst><a:ln w="6350"><a:solidFill><a:schemeClr val="phClr"/></a:solidFill></a:ln><a:ln w="12700"><a:solidFill><a:schemeClr val="phClr"/></a:solidFill></a:ln><a:ln w="19050"><a:solidFill><a:schemeClr val="phClr"/></a:solidFill></a:ln></a:lnStyleLst><a:effectStyleLst><a:effectStyle><a:effectLst/></a:effectStyle><a:effectStyle><a:effectLst/></a:effectStyle><a:effectStyle><a:effectLst/></a:effectStyle></a:effectStyleLst><a:bgFillStyleLst><a:solidFill><a:schemeClr val="phClr"/></a:solidFill><a:solidFill><a:schemeClr val="phClr"/></a:solidFill><a:solidFill><a:schemeClr val="phClr"/></a:solidFill></a:bgFillStyleLst></a:fmtScheme></a:themeElements><a:objectDefaults/><a:extraClrSchemeLst/></a:theme>"#;
+
+/// Write the document as an Office Open XML presentation
+pub fn write<W: Write + Seek>(document: &Document, page_size: PageSize, writer: W) -> Result<()> {
+    let (width, height) = page_size;
+    let slide_height = SLIDE_WIDTH * height as i64 / width as i64;
+
+    let mut pptx = Pptx {
+        zip: ZipWriter::new(writer),
+        options: SimpleFileOptions::default().compression_method(CompressionMethod::Deflated),
+        language: language_tag(&document.language),
+        media: vec![],
+    };
+
+    let mut slides = vec![];
+    for (idx, slide) in document.slides.iter().enumerate() {
+        slides.push(pptx.write_slide(idx + 1, slide, slide_height)?);
+    }
+
+    pptx.write_presentation(document, &slides, slide_height)?;
+    pptx.zip.finish()?;
+    Ok(())
+}
+
+struct Pptx<W: Write + Seek> {
+    zip: ZipWriter<W>,
+    options: SimpleFileOptions,
+    language: String,
+    /// File extensions of the embedded images
+    media: Vec<&'static str>,
+}
+
+/// What a written slide needs to be referenced from the presentation
+struct WrittenSlide {
+    has_notes: bool,
+}
+
+/// Relationships of a slide part, `rId1` always being the slide layout
+struct Relationships {
+    entries: Vec<(String, String, bool)>,
+}
+
+impl Relationships {
+    fn new() -> Self {
+        Relationships { entries: vec![] }
+    }
+
+    /// Register a relationship and return its id
+    fn add(&mut self, kind: &str, target: &str, external: bool) -> String {
+        self.entries
+            .push((kind.to_string(), target.to_string(), external));
+        format!("rId{}", self.entries.len())
+    }
+
+    fn to_xml(&self) -> String {
+        let mut xml = format!(
+            r#"{}<Relationships xmlns="http://schemas.openxmlformats.org/package/2006/relationships">"#,
+            XML_HEADER
+        );
+
+        for (idx, (kind, target, external)) in self.entries.iter().enumerate() {
+            xml.push_str(&format!(
+                r#"<Relationship Id="rId{}" Type="{}/{}" Target="{}"{}/>"#,
+                idx + 1,
+                RELATIONSHIP,
+                kind,
+                xml_escape(target),
+                if *external {
+                    r#" TargetMode="External""#
+                } else {
+                    ""
+                }
+            ));
+        }
+
+        xml.push_str("</Relationships>");
+        xml
+    }
+}
+
+/// Shapes of a slide, stacked from top to bottom
+struct ShapeTree {
+    xml: String,
+    next_id: usize,
+    y: i64,
+}
+
+impl ShapeTree {
+    fn new() -> Self {
+        ShapeTree {
+            xml: String::new(),
+            next_id: 2,
+            y: EMU_PER_INCH * 2 / 5,
+        }
+    }
+
+    fn push_text_box(&mut self, paragraphs: &str, height: i64, fill: Option<&str>) {
+        let id = self.next_id;
+        let fill = fill
+            .map(|color| format!(r#"<a:solidFill><a:srgbClr val="{}"/></a:solidFill>"#, color))
+            .unwrap_or_default();
+
+        self.xml.push_str(&format!(
+            r#"<p:sp><p:nvSpPr><p:cNvPr id="{id}" name="TextBox {id}"/><p:cNvSpPr txBox="1"/><p:nvPr/></p:nvSpPr><p:spPr><a:xfrm><a:off x="{x}" y="{y}"/><a:ext cx="{cx}" cy="{cy}"/></a:xfrm><a:prstGeom prst="rect"><a:avLst/></a:prstGeom>{fill}</p:spPr><p:txBody><a:bodyPr wrap="square" rtlCol="0"><a:spAutoFit/></a:bodyPr><a:lstStyle/>{paragraphs}</p:txBody></p:sp>"#,
+            id = id,
+            x = MARGIN,
+            y = self.y,
+            cx = SLIDE_WIDTH - 2 * MARGIN,
+            cy = height,
+            fill = fill,
+            paragraphs = paragraphs,
+        ));
+
+        self.next_id += 1;
+        self.y += height + EMU_PER_POINT * 8;
+    }
+
+    fn push_picture(&mut self, relationship: &str, alt: &str, size: (i64, i64)) {
+        let id = self.next_id;
+        let (cx, cy) = size;
+
+        self.xml.push_str(&format!(
+            r#"<p:pic><p:nvPicPr><p:cNvPr id="{id}" name="Picture {id}" descr="{alt}"/><p:cNvPicPr><a:picLocks noChangeAspect="1"/></p:cNvPicPr><p:nvPr/></p:nvPicPr><p:blipFill><a:blip r:embed="{rel}"/><a:stretch><a:fillRect/></a:stretch></p:blipFill><p:spPr><a:xfrm><a:off x="{x}" y="{y}"/><a:ext cx="{cx}" cy="{cy}"/></a:xfrm><a:prstGeom prst="rect"><a:avLst/></a:prstGeom></p:spPr></p:pic>"#,
+            id = id,
+            alt = xml_escape(alt),
+            rel = relationship,
+            x = MARGIN + (SLIDE_WIDTH - 2 * MARGIN - cx) / 2,
+            y = self.y,
+            cx = cx,
+            cy = cy,
+        ));
+
+        self.next_id += 1;
+        self.y += cy + EMU_PER_POINT * 8;
+    }
+}
+
+impl<W: Write + Seek> Pptx<W> {
+    fn write_part(&mut self, name: &str, content: &str) -> Result<()> {
+        self.zip.start_file(name, self.options)?;
+        self.zip.write_all(content.as_bytes())?;
+        Ok(())
+    }
+
+    fn write_slide(
+        &mut self,
+        number: usize,
+        slide: &DocumentSlide,
+        slide_height: i64,
+    ) -> Result<WrittenSlide> {
+        let mut relationships = Relationships::new();
+        relationships.add("slideLayout", "../slideLayouts/slideLayout1.xml", false);
+
+        let mut shapes = ShapeTree::new();
+        // Consecutive text blocks share the same text box
+        let mut paragraphs = String::new();
+        let mut lines = 0;
+
+        let mut blocks = slide.blocks.iter().peekable();
+
+        // A leading heading is the slide title
+        if let Some(Block::Heading { content, .. }) = blocks.peek() {
+            let title = self.paragraph(content, TITLE_SIZE, "", &mut relationships, true);
+            shapes.push_text_box(&title, text_height(content, TITLE_SIZE), None);
+            blocks.next();
+        }
+
+        for block in blocks {
+            match block {
+                Block::Code { code, .. } => {
+                    self.flush_text(&mut shapes, &mut paragraphs, &mut lines);
+                    let code_paragraphs = code
+                        .trim_end_matches('\n')
+                        .lines()
+                        .map(|line| {
+                            let span = Span {
+                                text: line.to_string(),
+                                code: true,
+                                ..Span::default()
+                            };
+                            self.paragraph(&[span], CODE_SIZE, "", &mut relationships, false)
+                        })
+                        .collect::<String>();
+                    let height = line_height(CODE_SIZE) * code.lines().count().max(1) as i64
+                        + EMU_PER_POINT * 8;
+                    shapes.push_text_box(&code_paragraphs, height, Some("F2F2F2"));
+                }
+                Block::Image { src, alt } => {
+                    self.flush_text(&mut shapes, &mut paragraphs, &mut lines);
+                    let available = (slide_height - shapes.y - MARGIN).max(EMU_PER_INCH);
+                    match self.add_image(src, available, &mut relationships)? {
+                        Some((relationship, size)) => shapes.push_picture(&relationship, alt, size),
+                        None => {
+                            let span = Span {
+                                text: alt.clone(),
+                                italic: true,
+                                ..Span::default()
+                            };
+                            paragraphs.push_str(&self.paragraph(
+                                &[span],
+                                TEXT_SIZE,
+                                "",
+                                &mut relationships,
+                                false,
+                            ));
+                            lines += 1;
+                        }
+                    }
+                }
+                Block::Heading { content, .. } => {
+                    let mut content = content.clone();
+                    content.iter_mut().for_each(|span| span.bold = true);
+                    paragraphs.push_str(&self.paragraph(
+                        &content,
+                        TEXT_SIZE + 4,
+                        "",
+                        &mut relationships,
+                        false,
+                    ));
+                    lines += text_lines(&content, TEXT_SIZE + 4);
+                }
+                Block::Paragraph(content) => {
+                    paragraphs.push_str(&self.paragraph(
+                        content,
+                        TEXT_SIZE,
+                        "",
+                        &mut relationships,
+                        false,
+                    ));
+                    lines += text_lines(content, TEXT_SIZE);
+                }
+                Block::Quote(content) => {
+                    let mut content = content.clone();
+                    content.iter_mut().for_each(|span| span.italic = true);
+                    paragraphs.push_str(&self.paragraph(
+                        &content,
+                        TEXT_SIZE,
+                        r#"<a:pPr marL="457200"/>"#,
+                        &mut relationships,
+                        false,
+                    ));
+                    lines += text_lines(&content, TEXT_SIZE);
+                }
+                Block::List { ordered, items } => {
+                    for item in items {
+                        let bullet = if *ordered {
+                            r#"<a:buFont typeface="+mj-lt"/><a:buAutoNum type="arabicPeriod"/>"#
+                        } else {
+                            r#"<a:buFont typeface="Arial"/><a:buChar char="•"/>"#
+                        };
+                        let properties = format!(
+                            r#"<a:pPr marL="{}" lvl="{}" indent="-342900">{}</a:pPr>"#,
+                            342_900 * (item.level as i64 + 1),
+                            item.level,
+                            bullet
+                        );
+                        paragraphs.push_str(&self.paragraph(
+                            &item.content,
+                            TEXT_SIZE,
+                            &properties,
+                            &mut relationships,
+                            false,
+                        ));
+                        lines += text_lines(&item.content, TEXT_SIZE);
+                    }
+                }
+                Block::Table { header, rows } => {
+                    for line in table_lines(header, rows) {
+                        paragraphs.push_str(&self.paragraph(
+                            &line,
+                            TEXT_SIZE,
+                            "",
+                            &mut relationships,
+                            false,
+                        ));
+                        lines += text_lines(&line, TEXT_SIZE);
+                    }
+                }
+                Block::Rule => {}
+            }
+        }
+
+        self.flush_text(&mut shapes, &mut paragraphs, &mut lines);
+
+        let has_notes = !slide.notes.is_empty();
+        if has_notes {
+            relationships.add(
+                "notesSlide",
+                &format!("../notesSlides/notesSlide{}.xml", number),
+                false,
+            );
+            self.write_notes(number, slide)?;
+        }
+
+        let xml = format!(
+            r#"{}<p:sld {}><p:cSld><p:spTree>{}{}</p:spTree></p:cSld><p:clrMapOvr><a:masterClrMapping/></p:clrMapOvr></p:sld>"#,
+            XML_HEADER, NAMESPACES, GROUP_SHAPE, shapes.xml
+        );
+
+        self.write_part(&format!("ppt/slides/slide{}.xml", number), &xml)?;
+        self.write_part(
+            &format!("ppt/slides/_rels/slide{}.xml.rels", number),
+            &relationships.to_xml(),
+        )?;
+
+        Ok(WrittenSlide { has_notes })
+    }
+
+    fn flush_text(&mut self, shapes: &mut ShapeTree, paragraphs: &mut String, lines: &mut i64) {
+        if !paragraphs.is_empty() {
+            let height = line_height(TEXT_SIZE) * *lines + EMU_PER_POINT * 6 * *lines;
+            shapes.push_text_box(paragraphs, height, None);
+            paragraphs.clear();
+            *lines = 0;
+        }
+    }
+
+    /// Render a paragraph, `properties` being its `a:pPr` element
+    fn paragraph(
+        &self,
+        spans: &[Span],
+        size: i64,
+        properties: &str,
+        relationships: &mut Relationships,
+        title: bool,
+    ) -> String {
+        let properties = if properties.is_empty() {
+            r#"<a:pPr><a:spcBef><a:spcPts val="600"/></a:spcBef></a:pPr>"#
+        } else {
+            properties
+        };
+
+        let mut xml = format!("<a:p>{}", properties);
+        for span in spans {
+            let mut run_properties = format!(
+                r#"<a:rPr lang="{}" sz="{}" dirty="0""#,
+                self.language,
+                size * 100
+            );
+
+            if span.bold || title {
+                run_properties.push_str(r#" b="1""#);
+            }
+
+            if span.italic {
+                run_properties.push_str(r#" i="1""#);
+            }
+
+            run_properties.push('>');
+
+            if span.code {
+                run_properties.push_str(&format!(r#"<a:latin typeface="{}"/>"#, CODE_FONT));
+            }
+
+            if let Some(link) = &span.link {
+                let relationship = relationships.add("hyperlink", link, true);
+                run_properties.push_str(&format!(r#"<a:hlinkClick r:id="{}"/>"#, relationship));
+            }
+
+            run_properties.push_str("</a:rPr>");
+            xml.push_str(&format!(
+                "<a:r>{}<a:t>{}</a:t></a:r>",
+                run_properties,
+                xml_escape(&span.text)
+            ));
+        }
+
+        xml.push_str(&format!(
+            r#"<a:endParaRPr lang="{}" sz="{}" dirty="0"/></a:p>"#,
+            self.language,
+            size * 100
+        ));
+        xml
+    }
+
+    /// Embed a local image, returning its relationship and its size on the slide
+    fn add_image(
+        &mut self,
+        src: &str,
+        available_height: i64,
+        relationships: &mut Relationships,
+    ) -> Result<Option<(String, (i64, i64))>> {
+        let image = match local_image(src) {
+            Some(image) => image,
+            None => return Ok(None),
+        };
+
+        let (extension, width, height) = (image.extension, image.width, image.height);
+        self.media.push(extension);
+        let name = format!("image{}.{}", self.media.len(), extension);
+        let content = fs::read(&image.path)?;
+        self.zip
+            .start_file(format!("ppt/media/{}", name), self.options)?;
+        self.zip.write_all(&content)?;
+
+        let relationship = relationships.add("image", &format!("../media/{}", name), false);
+
+        // Images are displayed at 96 dpi, scaled down to fit the slide
+        let mut cx = width as i64 * EMU_PER_INCH / 96;
+        let mut cy = height as i64 * EMU_PER_INCH / 96;
+        let max_width = SLIDE_WIDTH - 2 * MARGIN;
+
+        if cx > max_width {
+            cy = cy * max_width / cx;
+            cx = max_width;
+        }
+
+        if cy > available_height {
+            cx = cx * available_height / cy;
+            cy = available_height;
+        }
+
+        Ok(Some((relationship, (cx, cy))))
+    }
+
+    fn write_notes(&mut self, number: usize, slide: &DocumentSlide) -> Result<()> {
+        let paragraphs = slide
+            .notes
+            .iter()
+            .map(|block| {
+                block
+                    .plain_text()
+                    .lines()
+                    .map(|line| {
+                        format!(
+                            r#"<a:p><a:r><a:rPr lang="{}" dirty="0"/><a:t>{}</a:t></a:r></a:p>"#,
+                            self.language,
+                            xml_escape(line)
+                        )
+                    })
+                    .collect::<String>()
+            })
+            .collect::<String>();
+
+        let xml = format!(
+            r#"{}<p:notes {}><p:cSld><p:spTree>{}<p:sp><p:nvSpPr><p:cNvPr id="2" name="Slide Image Placeholder 1"/><p:cNvSpPr><a:spLocks noGrp="1" noRot="1" noChangeAspect="1"/></p:cNvSpPr><p:nvPr><p:ph type="sldImg"/></p:nvPr></p:nvSpPr><p:spPr/></p:sp><p:sp><p:nvSpPr><p:cNvPr id="3" name="Notes Placeholder 2"/><p:cNvSpPr><a:spLocks noGrp="1"/></p:cNvSpPr><p:nvPr><p:ph type="body" idx="1"/></p:nvPr></p:nvSpPr><p:spPr/><p:txBody><a:bodyPr/><a:lstStyle/>{}</p:txBody></p:sp></p:spTree></p:cSld><p:clrMapOvr><a:masterClrMapping/></p:clrMapOvr></p:notes>"#,
+            XML_HEADER, NAMESPACES, GROUP_SHAPE, paragraphs
+        );
+
+        let mut relationships = Relationships::new();
+        relationships.add("notesMaster", "../notesMasters/notesMaster1.xml", false);
+        relationships.add("slide", &format!("../slides/slide{}.xml", number), false);
+
+        self.write_part(&format!("ppt/notesSlides/notesSlide{}.xml", number), &xml)?;
+        self.write_part(
+            &format!("ppt/notesSlides/_rels/notesSlide{}.xml.rels", number),
+            &relationships.to_xml(),
+        )
+    }
+
+    fn write_presentation(
+        &mut self,
+        document: &Document,
+        slides: &[WrittenSlide],
+        slide_height: i64,
+    ) -> Result<()> {
+        // Content types
+        let mut content_types = format!(
+            r#"{}<Types xmlns="http://schemas.openxmlformats.org/package/2006/content-types"><Default Extension="rels" ContentType="application/vnd.openxmlformats-package.relationships+xml"/><Default Extension="xml" ContentType="application/xml"/><Default Extension="png" ContentType="image/png"/><Default Extension="jpeg" ContentType="image/jpeg"/><Default Extension="gif" ContentType="image/gif"/>"#,
+            XML_HEADER
+        );
+
+        let overrides = [
+            ("/ppt/presentation.xml", "presentationml.presentation.main"),
+            (
+                "/ppt/slideMasters/slideMaster1.xml",
+                "presentationml.slideMaster",
+            ),
+            (
+                "/ppt/slideLayouts/slideLayout1.xml",
+                "presentationml.slideLayout",
+            ),
+            (
+                "/ppt/notesMasters/notesMaster1.xml",
+                "presentationml.notesMaster",
+            ),
+            ("/ppt/theme/theme1.xml", "theme"),
+            ("/ppt/theme/theme2.xml", "theme"),
+            ("/ppt/presProps.xml", "presentationml.presProps"),
+            ("/ppt/viewProps.xml", "presentationml.viewProps"),
+            ("/ppt/tableStyles.xml", "presentationml.tableStyles"),
+            ("/docProps/app.xml", "extended-properties"),
+        ];
+
+        for (part, kind) in overrides.iter() {
+            content_types.push_str(&format!(
+                r#"<Override PartName="{}" ContentType="application/vnd.openxmlformats-officedocument.{}+xml"/>"#,
+                part, kind
+            ));
+        }
+
+        content_types.push_str(r#"<Override PartName="/docProps/core.xml" ContentType="application/vnd.openxmlformats-package.core-properties+xml"/>"#);
+
+        for (idx, slide) in slides.iter().enumerate() {
+            content_types.push_str(&format!(
+                r#"<Override PartName="/ppt/slides/slide{}.xml" ContentType="application/vnd.openxmlformats-officedocument.presentationml.slide+xml"/>"#,
+                idx + 1
+            ));
+
+            if slide.has_notes {
+                content_types.push_str(&format!(
+                    r#"<Override PartName="/ppt/notesSlides/notesSlide{}.xml" ContentType="application/vnd.openxmlformats-officedocument.presentationml.notesSlide+xml"/>"#,
+                    idx + 1
+                ));
+            }
+        }
+
+        content_types.push_str("</Types>");
+        self.write_part("[Content_Types].xml", &content_types)?;
+
+        // Package relationships and properties
+        let mut package = Relationships::new();
+        package.add("officeDocument", "ppt/presentation.xml", false);
+        package.add("extended-properties", "docProps/app.xml", false);
+        let package = package.to_xml().replace(
+            "</Relationships>",
+            r#"<Relationship Id="rId3" Type="http://schemas.openxmlformats.org/package/2006/relationships/metadata/core-properties" Target="docProps/core.xml"/></Relationships>"#,
+        );
+        self.write_part("_rels/.rels", &package)?;
+
+        let creator = document
+            .author
+            .as_deref()
+            .map(|author| format!("<dc:creator>{}</dc:creator>", xml_escape(author)))
+            .unwrap_or_default();
+
+        self.write_part(
+            "docProps/core.xml",
+            &format!(
+                r#"{}<cp:coreProperties xmlns:cp="http://schemas.openxmlformats.org/package/2006/metadata/core-properties" xmlns:dc="http://purl.org/dc/elements/1.1/" xmlns:dcterms="http://purl.org/dc/terms/" xmlns:xsi="http://www.w3.org/2001/XMLSchema-instance"><dc:title>{}</dc:title>{}<dc:language>{}</dc:language></cp:coreProperties>"#,
+                XML_HEADER,
+                xml_escape(&document.title),
+                creator,
+                self.language
+            ),
+        )?;
+
+        self.write_part(
+            "docProps/app.xml",
+            &format!(
+                r#"{}<Properties xmlns="http://schemas.openxmlformats.org/officeDocument/2006/extended-properties"><Application>Unveil</Application><Slides>{}</Slides></Properties>"#,
+                XML_HEADER,
+                slides.len()
+            ),
+        )?;
+
+        // Presentation
+        let mut presentation_relationships = Relationships::new();
+        presentation_relationships.add("slideMaster", "slideMasters/slideMaster1.xml", false);
+        presentation_relationships.add("notesMaster", "notesMasters/notesMaster1.xml", false);
+        presentation_relationships.add("theme", "theme/theme1.xml", false);
+        presentation_relationships.add("presProps", "presProps.xml", false);
+        presentation_relationships.add("viewProps", "viewProps.xml", false);
+        presentation_relationships.add("tableStyles", "tableStyles.xml", false);
+
+        let slide_ids = (0..slides.len())
+            .map(|idx| {
+                let relationship = presentation_relationships.add(
+                    "slide",
+                    &format!("slides/slide{}.xml", idx + 1),
+                    false,
+                );
+                format!(r#"<p:sldId id="{}" r:id="{}"/>"#, 256 + idx, relationship)
+            })
+            .collect::<String>();
+
+        self.write_part(
+            "ppt/presentation.xml",
+            &format!(
+                r#"{}<p:presentation {} saveSubsetFonts="1"><p:sldMasterIdLst><p:sldMasterId id="2147483648" r:id="rId1"/></p:sldMasterIdLst><p:notesMasterIdLst><p:notesMasterId r:id="rId2"/></p:notesMasterIdLst><p:sldIdLst>{}</p:sldIdLst><p:sldSz cx="{}" cy="{}"/><p:notesSz cx="6858000" cy="9144000"/></p:presentation>"#,
+                XML_HEADER, NAMESPACES, slide_ids, SLIDE_WIDTH, slide_height
+            ),
+        )?;
+        self.write_part(
+            "ppt/_rels/presentation.xml.rels",
+            &presentation_relationships.to_xml(),
+        )?;
+
+        self.write_part(
+            "ppt/presProps.xml",
+            &format!(r#"{}<p:presentationPr {}/>"#, XML_HEADER, NAMESPACES),
+        )?;
+        self.write_part(
+            "ppt/viewProps.xml",
+            &format!(r#"{}<p:viewPr {}/>"#, XML_HEADER, NAMESPACES),
+        )?;
+        self.write_part(
+            "ppt/tableStyles.xml",
+            &format!(
+                r#"{}<a:tblStyleLst xmlns:a="http://schemas.openxmlformats.org/drawingml/2006/main" def="{{5C22544A-7EE6-4342-B048-85BDC9FD1C3A}}"/>"#,
+                XML_HEADER
+            ),
+        )?;
+
+        // Masters, layout and themes
+        self.write_part(
+            "ppt/slideMasters/slideMaster1.xml",
+            &format!(
+                r#"{}<p:sldMaster {}><p:cSld>{}<p:spTree>{}</p:spTree></p:cSld>{}<p:sldLayoutIdLst><p:sldLayoutId id="2147483649" r:id="rId1"/></p:sldLayoutIdLst></p:sldMaster>"#,
+                XML_HEADER, NAMESPACES, BACKGROUND, GROUP_SHAPE, COLOR_MAP
+            ),
+        )?;
+        let mut master_relationships = Relationships::new();
+        master_relationships.add("slideLayout", "../slideLayouts/slideLayout1.xml", false);
+        master_relationships.add("theme", "../theme/theme1.xml", false);
+        self.write_part(
+            "ppt/slideMasters/_rels/slideMaster1.xml.rels",
+            &master_relationships.to_xml(),
+        )?;
+
+        self.write_part(
+            "ppt/slideLayouts/slideLayout1.xml",
+            &format!(
+                r#"{}<p:sldLayout {} type="blank" preserve="1"><p:cSld name="Blank"><p:spTree>{}</p:spTree></p:cSld><p:clrMapOvr><a:masterClrMapping/></p:clrMapOvr></p:sldLayout>"#,
+                XML_HEADER, NAMESPACES, GROUP_SHAPE
+            ),
+        )?;
+        let mut layout_relationships = Relationships::new();
+        layout_relationships.add("slideMaster", "../slideMasters/slideMaster1.xml", false);
+        self.write_part(
+            "ppt/slideLayouts/_rels/slideLayout1.xml.rels",
+            &layout_relationships.to_xml(),
+        )?;
+
+        self.write_part(
+            "ppt/notesMasters/notesMaster1.xml",
+            &format!(
+                r#"{}<p:notesMaster {}><p:cSld>{}<p:spTree>{}<p:sp><p:nvSpPr><p:cNvPr id="2" name="Slide Image Placeholder 1"/><p:cNvSpPr><a:spLocks noGrp="1" noRot="1" noChangeAspect="1"/></p:cNvSpPr><p:nvPr><p:ph type="sldImg" idx="2"/></p:nvPr></p:nvSpPr><p:spPr><a:xfrm><a:off x="381000" y="685800"/><a:ext cx="6096000" cy="3429000"/></a:xfrm><a:prstGeom prst="rect"><a:avLst/></a:prstGeom></p:spPr></p:sp><p:sp><p:nvSpPr><p:cNvPr id="3" name="Notes Placeholder 2"/><p:cNvSpPr><a:spLocks noGrp="1"/></p:cNvSpPr><p:nvPr><p:ph type="body" sz="quarter" idx="3"/></p:nvPr></p:nvSpPr><p:spPr><a:xfrm><a:off x="685800" y="4343400"/><a:ext cx="5486400" cy="4114800"/></a:xfrm><a:prstGeom prst="rect"><a:avLst/></a:prstGeom></p:spPr><p:txBody><a:bodyPr/><a:lstStyle/><a:p><a:endParaRPr lang="{}"/></a:p></p:txBody></p:sp></p:spTree></p:cSld>{}</p:notesMaster>"#,
+                XML_HEADER, NAMESPACES, BACKGROUND, GROUP_SHAPE, self.language, COLOR_MAP
+            ),
+        )?;
+        let mut notes_master_relationships = Relationships::new();
+        notes_master_relationships.add("theme", "../theme/theme2.xml", false);
+        self.write_part(
+            "ppt/notesMasters/_rels/notesMaster1.xml.rels",
+            &notes_master_relationships.to_xml(),
+        )?;
+
+        // Each master needs its own theme part
+        let theme = format!("{}{}", XML_HEADER, THEME);
+        self.write_part("ppt/theme/theme1.xml", &theme)?;
+        self.write_part("ppt/theme/theme2.xml", &theme)
+    }
+}
+
+fn line_height(size: i64) -> i64 {
+    size * EMU_PER_POINT * 6 / 5
+}
+
+fn text_lines(spans: &[Span], size: i64) -> i64 {
+    export::text_lines(spans, size * EMU_PER_POINT, SLIDE_WIDTH - 2 * MARGIN)
+}
+
+fn text_height(spans: &[Span], size: i64) -> i64 {
+    line_height(size) * text_lines(spans, size) + EMU_PER_POINT * 8
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{document::Document, export::pptx};
+    use std::io::{Cursor, Read};
+    use zip::ZipArchive;
+
+    #[test]
+    fn should_write_slides_and_notes() {
+        let document = Document::parse(
+            "demo",
+            "EN",
+            &[
+                (
+                    "slides/a.md".into(),
+                    "# Hello & world\n\n- one\n- [two](https://example.org)\n\n```rust\nfn main() {}\n```\n???\nSay hello"
+                        .into(),
+                ),
+                ("slides/b.md".into(), "Second slide".into()),
+            ],
+        );
+
+        let mut output = Cursor::new(vec![]);
+        pptx::write(&document, (1280, 720), &mut output).unwrap();
+
+        let mut archive = ZipArchive::new(output).unwrap();
+        let mut read = |name: &str| {
+            let mut content = String::new();
+            archive
+                .by_name(name)
+                .unwrap()
+                .read_to_string(&mut content)
+                .unwrap();
+            content
+        };
+
+        let presentation = read("ppt/presentation.xml");
+        assert!(presentation.contains(r#"<p:sldSz cx="12192000" cy="6858000"/>"#));
+        assert!(presentation.contains(r#"<p:sldId id="257" r:id="rId8"/>"#));
+
+        let slide = read("ppt/slides/slide1.xml");
+        assert!(slide.contains("<a:t>Hello &amp; world</a:t>"));
+        assert!(slide.contains(r#"<a:buChar char="•"/>"#));
+        assert!(
+            slide.contains(r#"<a:latin typeface="Courier New"/></a:rPr><a:t>fn main() {}</a:t>"#)
+        );
+
+        let relationships = read("ppt/slides/_rels/slide1.xml.rels");
+        assert!(relationships.contains(r#"Target="https://example.org" TargetMode="External""#));
+        assert!(relationships.contains("../notesSlides/notesSlide1.xml"));
+
+        assert!(read("ppt/notesSlides/notesSlide1.xml").contains("<a:t>Say hello</a:t>"));
+        assert!(!read("[Content_Types].xml").contains("notesSlide2.xml"));
+    }
+}
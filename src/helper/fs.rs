@@ -25,6 +25,17 @@ pub fn write_file(filename: &str, content: &[u8]) -> Result<()> {
         .map_err(|err| anyhow!("Could not write to file {} : {}", filename, err))
 }
 
+pub fn remove(filename: &str) -> Result<()> {
+    let file = PathBuf::from(filename);
+
+    if file.exists() {
+        std::fs::remove_file(file)
+            .map_err(|err| anyhow!("Could not remove file {} : {}", filename, err))?;
+    }
+
+    Ok(())
+}
+
 pub fn create_dir(dirname: &str) {
     let dir = PathBuf::from(dirname);
 
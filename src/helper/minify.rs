@@ -0,0 +1,57 @@
+use anyhow::{anyhow, Result};
+use lightningcss::stylesheet::{ParserOptions, PrinterOptions, StyleSheet};
+use minify_js::{Session, TopLevelMode};
+
+pub type Minifier = fn(&[u8]) -> Result<Vec<u8>>;
+
+pub fn html(content: &[u8]) -> Result<Vec<u8>> {
+    let mut cfg = minify_html::Cfg::spec_compliant();
+    cfg.minify_css = true;
+    cfg.minify_js = true;
+
+    Ok(minify_html::minify(content, &cfg))
+}
+
+pub fn css(content: &[u8]) -> Result<Vec<u8>> {
+    let content = std::str::from_utf8(content)?;
+    let stylesheet = StyleSheet::parse(content, ParserOptions::default())
+        .map_err(|err| anyhow!("Unable to minify css : {}", err))?;
+
+    let minified = stylesheet
+        .to_css(PrinterOptions {
+            minify: true,
+            ..PrinterOptions::default()
+        })
+        .map_err(|err| anyhow!("Unable to minify css : {}", err))?;
+
+    Ok(minified.code.into_bytes())
+}
+
+pub fn js(content: &[u8]) -> Result<Vec<u8>> {
+    let session = Session::new();
+    let mut output = vec![];
+
+    // Global mode keeps top level names intact, they are referenced from the html
+    minify_js::minify(&session, TopLevelMode::Global, content, &mut output)
+        .map_err(|err| anyhow!("Unable to minify javascript : {:?}", err))?;
+
+    Ok(output)
+}
+
+#[cfg(test)]
+pub mod test {
+    use crate::{
+        assets::{CSS, JS},
+        helper::minify,
+    };
+
+    #[test]
+    fn should_minify_bundled_assets() {
+        let css = minify::css(CSS).unwrap();
+        let js = minify::js(JS).unwrap();
+
+        assert!(css.len() < CSS.len());
+        assert!(js.len() < JS.len());
+        assert!(String::from_utf8(js).unwrap().contains("next_slide_right"));
+    }
+}
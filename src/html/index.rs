@@ -0,0 +1,70 @@
+use crate::config::DeckConfig;
+use horrorshow::{helper::doctype, html};
+
+/// Landing page of a multi-deck project, linking to every deck
+pub fn deck_index(project_name: &str, decks: &[DeckConfig], base_url: &str) -> String {
+    let html = html! {
+        : doctype::HTML;
+        html(lang="EN") {
+            head {
+                meta(charset="utf8");
+                title : project_name;
+                link(rel="stylesheet", href=format!("{}index.css", base_url));
+            }
+            body {
+                h1 : project_name;
+                ul(class="unveil-decks") {
+                    @ for deck in decks {
+                        li {
+                            a(href=format!("{}{}/", base_url, deck.name)) {
+                                : deck.title.as_deref().unwrap_or(&deck.name);
+                            }
+                            @ if let Some(author) = &deck.author {
+                                span(class="unveil-deck-author") : author;
+                            }
+                            @ if let Some(date) = &deck.date {
+                                time(class="unveil-deck-date", datetime=date) : date;
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    };
+
+    format!("{}", html)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{config::DeckConfig, html::deck_index};
+
+    #[test]
+    fn should_list_decks() {
+        let decks = vec![
+            DeckConfig {
+                name: "rust-intro".into(),
+                title: Some("Intro to Rust".into()),
+                author: Some("Ferris".into()),
+                date: Some("2026-01-10".into()),
+                slides: vec![],
+                theme: None,
+            },
+            DeckConfig {
+                name: "async".into(),
+                title: None,
+                author: None,
+                date: None,
+                slides: vec![],
+                theme: None,
+            },
+        ];
+
+        let output = deck_index("talks", &decks, "");
+
+        assert!(output.contains(r#"<a href="rust-intro/">Intro to Rust</a>"#));
+        assert!(output.contains(r#"<span class="unveil-deck-author">Ferris</span>"#));
+        assert!(output.contains(r#"<time class="unveil-deck-date" datetime="2026-01-10">"#));
+        assert!(output.contains(r#"<a href="async/">async</a>"#));
+    }
+}
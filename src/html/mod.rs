@@ -1,23 +1,62 @@
-use crate::html::preprocessor::Preprocessor;
-use anyhow::{anyhow, Result};
+use crate::{
+    config::PrintConfig,
+    html::{preprocessor::Preprocessor, toc::Toc},
+};
+use anyhow::Result;
 use horrorshow::{helper::doctype, html, prelude::*};
 use once_cell::sync::Lazy;
 use pulldown_cmark::{html, Options, Parser};
 use regex::Regex;
 use sass_rs::Options as SassOption;
+use std::fmt;
 
+mod index;
 mod preprocessor;
+mod slide;
+pub(crate) mod toc;
 
-// from zola https://github.com/getzola/zola/blob/1972e58823417a58eb1cc646ee346e7c3b04addb/components/front_matter/src/lib.rs
-static PAGE_RE: Lazy<Regex> = Lazy::new(|| {
-    Regex::new(r"^[[:space:]]*\+\+\+\r?\n((?s).*?(?-s))\+\+\+\r?\n?((?s).*(?-s))$").unwrap()
-});
+pub use index::deck_index;
+pub use slide::Slide;
+
+// Position of libsass errors, `on line 2:12 of stdin`
+static SASS_LINE_RE: Lazy<Regex> = Lazy::new(|| Regex::new(r"on line (\d+):\d+").unwrap());
+
+/// Style matter of a slide that does not compile
+#[derive(Debug)]
+pub struct StyleError {
+    /// Index of the slide in the deck
+    pub slide: usize,
+    /// 1-based line in the style matter, when known
+    pub line: Option<usize>,
+    pub message: String,
+}
+
+impl fmt::Display for StyleError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Unable to compile style matter : {}", self.message)
+    }
+}
+
+impl std::error::Error for StyleError {}
 
 pub struct HtmlBuilder {
     pub html: String,
     pub scss: String,
     pub markdown: Vec<String>,
     live_reload: bool,
+    release: bool,
+    title: String,
+    /// Url of the page directory
+    base_url: String,
+    /// Url of the bundled assets, shared between decks
+    assets_url: String,
+    deck_theme: bool,
+    /// Highlight the upcoming section on repeated agenda slides
+    toc_highlight: bool,
+    /// Names of the slides used in their url, `#/name`
+    slide_names: Vec<String>,
+    /// Slide index and first line of each style matter block in `scss`
+    stylematter_lines: Vec<(usize, usize)>,
 }
 
 impl HtmlBuilder {
@@ -29,27 +68,43 @@ impl HtmlBuilder {
         let css = if !self.scss.is_empty() {
             Some(
                 sass_rs::compile_string(&self.scss, SassOption::default())
-                    .map_err(|err| anyhow!("Unable to compile style matter : {}", err))?,
+                    .map_err(|err| self.style_error(&err))?,
             )
         } else {
             None
         };
 
         let has_user_css = css.as_ref().is_some();
+        // Release builds only reference the assets the slides actually use
+        let has_code = !self.release || self.has_code_blocks();
+        let has_playpen = !self.release || self.has_playpen();
+        let url = |path: &str| format!("{}{}", self.assets_url, path);
+        let page_url = |path: &str| format!("{}{}", self.base_url, path);
+        let deck_theme = self.deck_theme;
         let html = html! {
             : doctype::HTML;
             html(lang="EN") {
                 head {
                     meta(charset="utf8");
-                    title : "Unveil";
-                    link(rel="stylesheet", href="unveil.css");
+                    title : &self.title;
+                    link(rel="stylesheet", href=url("slides.css"));
+                    link(rel="stylesheet", href=url("unveil.css"));
+                    |tmpl| {
+                        if deck_theme {
+                            tmpl << html !(link(rel="stylesheet", href=page_url("theme.css")));
+                        }
+                    }
                     |tmpl| {
                         if has_user_css {
-                            tmpl << html !(link(rel="stylesheet", href="user_css.css"));
+                            tmpl << html !(link(rel="stylesheet", href=page_url("user_css.css")));
+                        }
+                    }
+                    |tmpl| {
+                        if has_code {
+                            tmpl << html !(link(rel="stylesheet", href=url("highlight.css")));
                         }
                     }
-                    link(rel="stylesheet", href="highlight.css");
-                    link(rel="stylesheet", href="fontawesome/css/fontawesome.css");
+                    link(rel="stylesheet", href=url("fontawesome/css/fontawesome.css"));
                 }
                 body {
                    div(onclick="next_slide_right()", class="arrow-right bounce-in") {
@@ -60,12 +115,20 @@ impl HtmlBuilder {
                         i(class="fas fa-chevron-left");
                    }
                    : Raw(&self.html);
-                   script(src="highlight.js");
-                   script(src="clipboard.js");
-                   script(src="unveil.js");
+                   |tmpl| {
+                       if has_code {
+                           tmpl << html !(script(src=url("highlight.js")));
+                       }
+                   }
+                   |tmpl| {
+                       if has_playpen {
+                           tmpl << html !(script(src=url("clipboard.js")));
+                       }
+                   }
+                   script(src=url("unveil.js"));
                    |tmpl| {
                        if self.live_reload {
-                         tmpl << html !(script(src="livereload.js"));
+                         tmpl << html !(script(src=url("livereload.js")));
                        }
                    }
                 }
@@ -75,38 +138,150 @@ impl HtmlBuilder {
         Ok((css, format!("{}", html)))
     }
 
-    fn split_slylematters(slide_content: &str) -> (Option<String>, String) {
-        // No stylematters : return the content as it is
-        if !PAGE_RE.is_match(slide_content) {
-            return (None, slide_content.to_owned());
-        }
+    /// Build a printable version of the slides, one slide per page.
+    /// Must be called after `build`.
+    pub fn build_print(&self, print: &PrintConfig) -> Result<String> {
+        let (width, height) = print.page_size()?;
+        let has_user_css = !self.scss.is_empty();
+        let has_code = !self.release || self.has_code_blocks();
+        let url = |path: &str| format!("{}{}", self.assets_url, path);
+        let page_url = |path: &str| format!("{}{}", self.base_url, path);
+        let deck_theme = self.deck_theme;
+        let page_style = format!(
+            "@page {{ size: {w}px {h}px; margin: 0; }} :root {{ --page-width: {w}px; --page-height: {h}px; }}",
+            w = width,
+            h = height
+        );
+
+        let html = html! {
+            : doctype::HTML;
+            html(lang="EN") {
+                head {
+                    meta(charset="utf8");
+                    title : &self.title;
+                    link(rel="stylesheet", href=url("slides.css"));
+                    link(rel="stylesheet", href=url("unveil.css"));
+                    |tmpl| {
+                        if deck_theme {
+                            tmpl << html !(link(rel="stylesheet", href=page_url("theme.css")));
+                        }
+                    }
+                    |tmpl| {
+                        if has_user_css {
+                            tmpl << html !(link(rel="stylesheet", href=page_url("user_css.css")));
+                        }
+                    }
+                    |tmpl| {
+                        if has_code {
+                            tmpl << html !(link(rel="stylesheet", href=url("highlight.css")));
+                        }
+                    }
+                    link(rel="stylesheet", href=url("fontawesome/css/fontawesome.css"));
+                    link(rel="stylesheet", href=url("print.css"));
+                    style : Raw(&page_style);
+                }
+                body(class=if print.notes { "unveil-print with-notes" } else { "unveil-print" }) {
+                    : Raw(&self.html);
+                    |tmpl| {
+                        if has_code {
+                            tmpl << html! {
+                                script(src=url("highlight.js"));
+                                script {
+                                    : Raw("document.querySelectorAll('code').forEach(block => hljs.highlightBlock(block));")
+                                }
+                            };
+                        }
+                    }
+                }
+            }
+        };
+
+        Ok(format!("{}", html))
+    }
+
+    /// Whether the built slides contain code, either inline or in code blocks
+    pub fn has_code_blocks(&self) -> bool {
+        self.html.contains("<code")
+    }
 
-        // 2. extract the style matter and the content
-        let caps = PAGE_RE.captures(slide_content).unwrap();
-        // caps[0] is the full match
-        // caps[1] => style matter
-        // caps[2] => content
-        (Some(caps[1].to_string()), caps[2].to_string())
+    /// Whether the built slides contain rust playground buttons
+    pub fn has_playpen(&self) -> bool {
+        self.html.contains("btn-playpen")
+    }
+
+    /// Locate a libsass error in the style matter of its slide
+    fn style_error(&self, err: &str) -> StyleError {
+        let message = err
+            .lines()
+            .next()
+            .unwrap_or_default()
+            .trim_start_matches("Error: ")
+            .to_string();
+        let line = SASS_LINE_RE
+            .captures(err)
+            .and_then(|caps| caps[1].parse::<usize>().ok());
+        let block = line.and_then(|line| {
+            self.stylematter_lines
+                .iter()
+                .rev()
+                .find(|(_, start)| *start <= line)
+                .map(|(slide, start)| (*slide, line - start + 1))
+        });
+
+        match block {
+            Some((slide, line)) => StyleError {
+                slide,
+                line: Some(line),
+                message,
+            },
+            None => StyleError {
+                slide: self
+                    .stylematter_lines
+                    .first()
+                    .map_or(0, |(slide, _)| *slide),
+                line: None,
+                message,
+            },
+        }
     }
 
     fn markdown_to_html(&mut self) {
         let mut html_ouput = String::new();
         let mut scss_output = String::new();
-        self.markdown
+        let mut stylematter_lines = vec![];
+        let slides: Vec<Slide> = self
+            .markdown
             .iter()
-            .map(|content| HtmlBuilder::split_slylematters(content))
+            .map(|content| Slide::parse(content))
+            .collect();
+        let toc = Toc::new(
+            &slides
+                .iter()
+                .map(|slide| slide.content.clone())
+                .collect::<Vec<String>>(),
+        );
+
+        slides
+            .into_iter()
             .enumerate()
-            .map(|(idx, (stylematter, markdown))| {
-                let parser = Parser::new_ext(&markdown, Options::empty());
-                let mut html = String::new();
-                html::push_html(&mut html, parser);
-                (idx, html, stylematter)
+            .map(|(idx, slide)| {
+                let html = if toc::has_placeholder(&slide.content) {
+                    let agenda = toc.render(idx, self.toc_highlight);
+                    HtmlBuilder::render_markdown(&toc::expand_placeholder(&slide.content, &agenda))
+                } else {
+                    HtmlBuilder::render_markdown(&slide.content)
+                };
+                let notes = slide.notes.as_deref().map(HtmlBuilder::render_markdown);
+                (idx, html, slide.stylematter, notes)
             })
-            .for_each(|(idx, html, stylematter)| {
+            .for_each(|(idx, html, stylematter, notes)| {
+                let name = self.slide_names.get(idx).map(String::as_str);
+                let slide = idx;
                 let idx = &format!("unveil-slide-{}", idx);
 
                 // If there is a style matter block wrap the inner scss in the section id block
                 if let Some(stylematter) = stylematter {
+                    stylematter_lines.push((slide, scss_output.matches('\n').count() + 1));
                     let scss_block = &format!("#{} {{ {} }}", idx, stylematter);
                     scss_output.push_str(scss_block);
                 }
@@ -115,28 +290,99 @@ impl HtmlBuilder {
                 html_ouput.push_str(&format!(
                     "{}",
                     html! {
-                        section(id=idx) { article { : Raw(&html) } }
+                        section(id=idx, data-name?=name) {
+                            article { : Raw(&html) }
+                            // Speaker notes are hidden from the audience
+                            @ if let Some(notes) = &notes {
+                                aside(class="notes", hidden?=true) { : Raw(notes) }
+                            }
+                        }
                     }
                 ));
             });
 
         self.scss = scss_output;
+        self.stylematter_lines = stylematter_lines;
         self.html = html_ouput;
     }
 
+    /// Render a single slide content, with its custom classes
+    pub(crate) fn render_slide(markdown: &str) -> String {
+        Preprocessor::insert_user_class(&HtmlBuilder::render_markdown(markdown))
+    }
+
+    fn render_markdown(markdown: &str) -> String {
+        let parser = Parser::new_ext(markdown, Options::empty());
+        let mut html = String::new();
+        html::push_html(&mut html, parser);
+        html
+    }
+
     pub fn new(markdown: Vec<String>, live_reload: bool) -> Self {
         HtmlBuilder {
             markdown,
             live_reload,
+            release: false,
+            title: "Unveil".to_string(),
+            base_url: String::new(),
+            assets_url: String::new(),
+            deck_theme: false,
+            toc_highlight: false,
+            slide_names: vec![],
+            stylematter_lines: vec![],
             html: String::new(),
             scss: String::new(),
         }
     }
+
+    pub fn with_release(mut self, release: bool) -> Self {
+        self.release = release;
+        self
+    }
+
+    /// Set the url of both the page and the bundled assets
+    pub fn with_base_url(mut self, base_url: &str) -> Self {
+        self.base_url = base_url.to_owned();
+        self.assets_url = base_url.to_owned();
+        self
+    }
+
+    /// Set the url of the bundled assets when they are not located next to the page
+    pub fn with_assets_url(mut self, assets_url: &str) -> Self {
+        self.assets_url = assets_url.to_owned();
+        self
+    }
+
+    pub fn with_title(mut self, title: &str) -> Self {
+        self.title = title.to_owned();
+        self
+    }
+
+    /// Link the `theme.css` file located next to the page, after the project theme
+    pub fn with_deck_theme(mut self, deck_theme: bool) -> Self {
+        self.deck_theme = deck_theme;
+        self
+    }
+
+    /// Name the slides, usually after their markdown file, to address them as `#/name`
+    pub fn with_slide_names(mut self, slide_names: Vec<String>) -> Self {
+        self.slide_names = slide_names;
+        self
+    }
+
+    /// Mark the section following each `{{toc}}` agenda slide as the current one
+    pub fn with_toc_highlight(mut self, toc_highlight: bool) -> Self {
+        self.toc_highlight = toc_highlight;
+        self
+    }
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::html::HtmlBuilder;
+    use crate::{
+        config::PrintConfig,
+        html::{HtmlBuilder, StyleError},
+    };
 
     #[test]
     fn should_replace_custom_classes() {
@@ -155,4 +401,138 @@ mod tests {
 
         assert!(output.1.contains("<p>Hello</p>"));
     }
+
+    #[test]
+    fn should_render_hidden_speaker_notes() {
+        let mut preprocessor = HtmlBuilder::new(vec!["# Hello\n???\nSay *hello*".into()], true);
+
+        let output = preprocessor.build().unwrap();
+
+        assert!(output.1.contains(
+            r#"<article><h1>Hello</h1>
+</article><aside class="notes" hidden><p>Say <em>hello</em></p>
+</aside>"#
+        ));
+    }
+
+    #[test]
+    fn should_build_print_page_with_notes() {
+        let mut preprocessor = HtmlBuilder::new(vec!["# Hello\n???\nSay hello".into()], true);
+        preprocessor.build().unwrap();
+
+        let output = preprocessor
+            .build_print(&PrintConfig {
+                aspect_ratio: "4:3".into(),
+                notes: true,
+            })
+            .unwrap();
+
+        assert!(output.contains("@page { size: 1280px 960px; margin: 0; }"));
+        assert!(output.contains(r#"<body class="unveil-print with-notes">"#));
+        assert!(output.contains(r#"<aside class="notes" hidden>"#));
+        assert!(!output.contains("unveil.js"));
+    }
+
+    #[test]
+    fn should_skip_unused_scripts_in_release() {
+        let mut preprocessor = HtmlBuilder::new(vec!["Hello".into()], false).with_release(true);
+
+        let output = preprocessor.build().unwrap();
+
+        assert!(!output.1.contains("highlight.js"));
+        assert!(!output.1.contains("clipboard.js"));
+        assert!(!output.1.contains("livereload.js"));
+    }
+
+    #[test]
+    fn should_prefix_assets_with_base_url() {
+        let mut preprocessor =
+            HtmlBuilder::new(vec!["Hello".into()], true).with_base_url("/our-talk/");
+
+        let output = preprocessor.build().unwrap();
+
+        assert!(output.1.contains(r#"href="/our-talk/unveil.css""#));
+        assert!(output.1.contains(r#"src="/our-talk/unveil.js""#));
+        assert!(output.1.contains(r#"src="/our-talk/livereload.js""#));
+    }
+
+    #[test]
+    fn should_link_shared_assets_from_deck() {
+        let mut preprocessor = HtmlBuilder::new(vec!["+++\ncolor: red;\n+++\nHello".into()], false)
+            .with_assets_url("../")
+            .with_deck_theme(true);
+
+        let output = preprocessor.build().unwrap();
+
+        assert!(output.1.contains(r#"href="../unveil.css""#));
+        assert!(output.1.contains(r#"href="theme.css""#));
+        assert!(output.1.contains(r#"href="user_css.css""#));
+    }
+
+    #[test]
+    fn should_replace_toc_placeholder_with_agenda() {
+        let slides = vec![
+            "# Agenda\n\n{{toc}}".into(),
+            "# Intro [class=\"red\"]".into(),
+            "## Why".into(),
+            "# Agenda\n\n{{toc}}".into(),
+            "# Usage".into(),
+        ];
+        let mut preprocessor = HtmlBuilder::new(slides, false).with_toc_highlight(true);
+
+        let output = preprocessor.build().unwrap();
+
+        let first = r##"<nav class="unveil-toc highlight"><ol><li class="toc-level-1 current"><a href="#/2">Intro</a></li><li class="toc-level-2 current"><a href="#/3">Why</a></li><li class="toc-level-1"><a href="#/5">Usage</a></li></ol></nav>"##;
+        let second = r##"<nav class="unveil-toc highlight"><ol><li class="toc-level-1"><a href="#/2">Intro</a></li><li class="toc-level-2"><a href="#/3">Why</a></li><li class="toc-level-1 current"><a href="#/5">Usage</a></li></ol></nav>"##;
+        assert!(output.1.contains(first));
+        assert!(output.1.contains(second));
+        assert!(!output.1.contains("{{toc}}"));
+    }
+
+    #[test]
+    fn should_keep_toc_placeholder_in_code() {
+        let slides = vec![
+            "# Agenda\n\nWrite `{{toc}}` to list the slides\n\n```\n{{toc}}\n```".into(),
+            "# Usage".into(),
+        ];
+        let mut preprocessor = HtmlBuilder::new(slides, false);
+
+        let output = preprocessor.build().unwrap();
+
+        assert!(output.1.contains("<code>{{toc}}</code>"));
+        assert!(output.1.contains("<pre><code>{{toc}}\n</code></pre>"));
+        assert!(!output.1.contains("unveil-toc"));
+    }
+
+    #[test]
+    fn should_name_slides() {
+        let mut preprocessor = HtmlBuilder::new(vec!["# Hello".into(), "# World".into()], false)
+            .with_slide_names(vec!["hello".into()]);
+
+        let output = preprocessor.build().unwrap();
+
+        assert!(output
+            .1
+            .contains(r#"<section id="unveil-slide-0" data-name="hello">"#));
+        assert!(output.1.contains(r#"<section id="unveil-slide-1">"#));
+    }
+
+    #[test]
+    fn should_locate_style_matter_errors() {
+        let mut preprocessor = HtmlBuilder::new(
+            vec![
+                "+++\ncolor: red;\n+++\n# Hello".into(),
+                "# World".into(),
+                "+++\ncolor: blue;\n\nh1 { color: $accent; }\n+++\n# Again".into(),
+            ],
+            false,
+        );
+
+        let err = preprocessor.build().unwrap_err();
+        let err = err.downcast_ref::<StyleError>().unwrap();
+
+        assert_eq!(err.slide, 2);
+        assert_eq!(err.line, Some(3));
+        assert_eq!(err.message, r#"Undefined variable: "$accent"."#);
+    }
 }
@@ -69,6 +69,7 @@ impl Preprocessor {
 #[cfg(test)]
 pub mod test {
     use crate::html::preprocessor::{Preprocessor, MD_CLASS_END, MD_CLASS_START, RUST_CODE_TAG};
+    use horrorshow::html;
 
     #[test]
     fn should_insert_playpen_buttons() {
@@ -0,0 +1,100 @@
+use once_cell::sync::Lazy;
+use pulldown_cmark::{Event, Parser, Tag};
+use regex::Regex;
+use std::ops::Range;
+
+// from zola https://github.com/getzola/zola/blob/1972e58823417a58eb1cc646ee346e7c3b04addb/components/front_matter/src/lib.rs
+static PAGE_RE: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r"^[[:space:]]*\+\+\+\r?\n((?s).*?(?-s))\+\+\+\r?\n?((?s).*(?-s))$").unwrap()
+});
+
+// Speaker notes follow a line containing only `???`
+static NOTES_RE: Lazy<Regex> = Lazy::new(|| Regex::new(r"(?m)^\?\?\?[[:blank:]]*\r?$").unwrap());
+
+/// A markdown slide split into its stylematter, content and speaker notes
+pub struct Slide {
+    pub stylematter: Option<String>,
+    pub content: String,
+    pub notes: Option<String>,
+}
+
+impl Slide {
+    pub fn parse(markdown: &str) -> Slide {
+        let (stylematter, markdown) = Slide::split_slylematters(markdown);
+
+        // A `???` line inside a code block is code, not the notes separator
+        let code_blocks: Vec<Range<usize>> = Parser::new(&markdown)
+            .into_offset_iter()
+            .filter_map(|(event, range)| {
+                matches!(event, Event::Start(Tag::CodeBlock(_))).then_some(range)
+            })
+            .collect();
+        let separator = NOTES_RE.find_iter(&markdown).find(|separator| {
+            !code_blocks
+                .iter()
+                .any(|block| block.contains(&separator.start()))
+        });
+
+        let (content, notes) = match separator {
+            Some(separator) => (
+                markdown[..separator.start()].to_string(),
+                Some(markdown[separator.end()..].trim().to_string()),
+            ),
+            None => (markdown, None),
+        };
+
+        Slide {
+            stylematter,
+            content,
+            notes,
+        }
+    }
+
+    fn split_slylematters(slide_content: &str) -> (Option<String>, String) {
+        // No stylematters : return the content as it is
+        if !PAGE_RE.is_match(slide_content) {
+            return (None, slide_content.to_owned());
+        }
+
+        // 2. extract the style matter and the content
+        let caps = PAGE_RE.captures(slide_content).unwrap();
+        // caps[0] is the full match
+        // caps[1] => style matter
+        // caps[2] => content
+        (Some(caps[1].to_string()), caps[2].to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::html::Slide;
+
+    #[test]
+    fn should_split_stylematter_content_and_notes() {
+        let slide = Slide::parse("+++\ncolor: red;\n+++\n# Hello\n???\nSay hello\n");
+
+        assert_eq!(slide.stylematter.as_deref(), Some("color: red;\n"));
+        assert_eq!(slide.content, "# Hello\n");
+        assert_eq!(slide.notes.as_deref(), Some("Say hello"));
+    }
+
+    #[test]
+    fn should_not_split_question_marks_inside_text() {
+        let slide = Slide::parse("# Hello ???\n");
+
+        assert_eq!(slide.content, "# Hello ???\n");
+        assert!(slide.notes.is_none());
+    }
+
+    #[test]
+    fn should_not_split_question_marks_inside_code_fences() {
+        let slide =
+            Slide::parse("# Notes\n```markdown\n# Slide\n???\nA note\n```\n???\nShow the fence\n");
+
+        assert_eq!(
+            slide.content,
+            "# Notes\n```markdown\n# Slide\n???\nA note\n```\n"
+        );
+        assert_eq!(slide.notes.as_deref(), Some("Show the fence"));
+    }
+}
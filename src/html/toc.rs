@@ -0,0 +1,167 @@
+use crate::document::first_heading;
+use horrorshow::html;
+use pulldown_cmark::{Event, Options, Parser, Tag};
+use std::ops::Range;
+
+/// Placeholder replaced with the table of contents
+pub const TOC_PLACEHOLDER: &str = "{{toc}}";
+
+/// Entry of the table of contents, one per slide starting with a heading
+struct TocEntry {
+    slide: usize,
+    level: u32,
+    title: String,
+}
+
+/// Table of contents of the deck, rendered on agenda slides
+pub struct Toc {
+    entries: Vec<TocEntry>,
+    /// Level of the section dividers, the highest heading level of the deck
+    section_level: u32,
+}
+
+impl Toc {
+    /// Collect the first heading of every slide but the agenda slides themselves
+    pub fn new(slides: &[String]) -> Toc {
+        let entries: Vec<TocEntry> = slides
+            .iter()
+            .enumerate()
+            .filter(|(_, content)| !has_placeholder(content))
+            .filter_map(|(slide, content)| {
+                first_heading(content).map(|(level, title)| TocEntry {
+                    slide,
+                    level,
+                    title,
+                })
+            })
+            .collect();
+
+        let section_level = entries.iter().map(|entry| entry.level).min().unwrap_or(1);
+
+        Toc {
+            entries,
+            section_level,
+        }
+    }
+
+    /// Render the agenda as a nested markdown list, for the documents exported without html
+    pub fn markdown(&self) -> String {
+        self.entries
+            .iter()
+            .map(|entry| {
+                format!(
+                    "{}- {}\n",
+                    "  ".repeat((entry.level - self.section_level) as usize),
+                    escape_markdown(&entry.title)
+                )
+            })
+            .collect()
+    }
+
+    /// Render the linked list for the agenda at index `slide`. When `highlight` is set
+    /// the section following the agenda slide, and its subsections, are marked as current.
+    pub fn render(&self, slide: usize, highlight: bool) -> String {
+        let current = if highlight {
+            self.entries
+                .iter()
+                .position(|entry| entry.slide > slide && entry.level == self.section_level)
+        } else {
+            None
+        };
+
+        // Entries from the current section divider up to the next one
+        let current_range = current.map(|start| {
+            let end = self.entries[start + 1..]
+                .iter()
+                .position(|entry| entry.level == self.section_level)
+                .map(|offset| start + 1 + offset)
+                .unwrap_or(self.entries.len());
+            start..end
+        });
+
+        let nav_class = if current.is_some() {
+            "unveil-toc highlight"
+        } else {
+            "unveil-toc"
+        };
+
+        let entries: Vec<(String, &TocEntry)> = self
+            .entries
+            .iter()
+            .enumerate()
+            .map(|(idx, entry)| {
+                let mut class = format!("toc-level-{}", entry.level - self.section_level + 1);
+                if current_range
+                    .as_ref()
+                    .is_some_and(|range| range.contains(&idx))
+                {
+                    class.push_str(" current");
+                }
+                (class, entry)
+            })
+            .collect();
+
+        format!(
+            "{}",
+            html! {
+                nav(class=nav_class) {
+                    ol {
+                        @ for (class, entry) in entries.iter() {
+                            li(class=class) {
+                                a(href=format!("#/{}", entry.slide + 1)) : &entry.title;
+                            }
+                        }
+                    }
+                }
+            }
+        )
+    }
+}
+
+/// Whether the slide has a `{{toc}}` paragraph, placeholders in code and text are left as is
+pub fn has_placeholder(markdown: &str) -> bool {
+    !placeholders(markdown).is_empty()
+}
+
+/// Replace the `{{toc}}` paragraphs of the slide markdown with the `agenda` block
+pub fn expand_placeholder(markdown: &str, agenda: &str) -> String {
+    let mut expanded = String::new();
+    let mut last_end = 0;
+    for range in placeholders(markdown) {
+        expanded.push_str(&markdown[last_end..range.start]);
+        // Blank lines end the agenda block before the rest of the slide
+        expanded.push_str(&format!("{}\n\n", agenda.trim_end()));
+        last_end = range.end;
+    }
+
+    expanded.push_str(&markdown[last_end..]);
+    expanded
+}
+
+/// Source ranges of the paragraphs made of the placeholder only
+fn placeholders(markdown: &str) -> Vec<Range<usize>> {
+    let events: Vec<(Event, Range<usize>)> = Parser::new_ext(markdown, Options::empty())
+        .into_offset_iter()
+        .collect();
+
+    events
+        .windows(3)
+        .filter_map(|window| match window {
+            [(Event::Start(Tag::Paragraph), range), (Event::Text(text), _), (Event::End(Tag::Paragraph), _)]
+                if text.trim() == TOC_PLACEHOLDER =>
+            {
+                Some(range.clone())
+            }
+            _ => None,
+        })
+        .collect()
+}
+
+fn escape_markdown(text: &str) -> String {
+    text.chars()
+        .flat_map(|c| match c {
+            '\\' | '`' | '*' | '_' | '[' | ']' | '<' | '>' | '#' | '!' | '|' => vec!['\\', c],
+            c => vec![c],
+        })
+        .collect()
+}
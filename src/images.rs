@@ -0,0 +1,364 @@
+use crate::config::{ImageConfig, ImageFormat};
+use anyhow::{anyhow, Result};
+use image::{
+    codecs::{jpeg::JpegEncoder, png::PngEncoder, webp::WebPEncoder},
+    imageops::FilterType,
+    DynamicImage, ImageFormat as SourceFormat,
+};
+use once_cell::sync::Lazy;
+use regex::Regex;
+use std::{
+    collections::{hash_map::DefaultHasher, BTreeSet},
+    fs,
+    hash::{Hash, Hasher},
+    path::{Path, PathBuf},
+};
+
+static IMG_SRC_RE: Lazy<Regex> = Lazy::new(|| Regex::new(r#"<img src="([^"]*)""#).unwrap());
+const CACHE_DIR: &str = ".unveil/cache/images";
+const OUTPUT_DIR: &str = "public/images";
+// Files of the output directory written by the last build, the only ones ever pruned
+const WRITTEN_LIST: &str = "written";
+
+pub struct ProcessedImage {
+    pub src: String,
+    pub width: u32,
+    pub height: u32,
+}
+
+/// Resize and convert the images referenced in the generated html.
+/// Processed images are cached in `.unveil/cache/images` so unchanged
+/// pictures are not decoded again on every rebuild.
+pub struct ImageProcessor {
+    config: ImageConfig,
+    base_url: String,
+    source_dirs: Vec<PathBuf>,
+    cache_dir: PathBuf,
+    output_dir: PathBuf,
+    /// File names of the output directory written for the rewritten pages
+    written: BTreeSet<String>,
+}
+
+impl ImageProcessor {
+    pub fn new(config: ImageConfig, base_url: &str) -> Self {
+        ImageProcessor {
+            config,
+            base_url: base_url.to_owned(),
+            // Image paths are resolved from the project root first, then from
+            // `public` where images were previously expected to live
+            source_dirs: vec![PathBuf::from("."), PathBuf::from("public")],
+            cache_dir: PathBuf::from(CACHE_DIR),
+            output_dir: PathBuf::from(OUTPUT_DIR),
+            written: BTreeSet::new(),
+        }
+    }
+
+    /// File names of the processed images written so far
+    pub fn written(&self) -> &BTreeSet<String> {
+        &self.written
+    }
+
+    /// Point every local `<img>` tag to its processed version and add
+    /// its dimensions and lazy loading attributes
+    pub fn rewrite_html(&mut self, html: &str) -> String {
+        let mut result = String::new();
+        let mut last_end = 0;
+
+        for caps in IMG_SRC_RE.captures_iter(html) {
+            let tag = caps.get(0).unwrap();
+            result.push_str(&html[last_end..tag.start()]);
+
+            match self.process(&caps[1]) {
+                Ok(Some(image)) => result.push_str(&format!(
+                    r#"<img src="{}" width="{}" height="{}" loading="lazy""#,
+                    image.src, image.width, image.height
+                )),
+                Ok(None) => result.push_str(tag.as_str()),
+                Err(err) => {
+                    eprintln!("Unable to process image {} : {}", &caps[1], err);
+                    result.push_str(tag.as_str());
+                }
+            }
+
+            last_end = tag.end();
+        }
+
+        result.push_str(&html[last_end..]);
+        result
+    }
+
+    fn process(&mut self, src: &str) -> Result<Option<ProcessedImage>> {
+        // Remote and inlined images are left untouched
+        if src.contains("://") || src.starts_with("//") || src.starts_with("data:") {
+            return Ok(None);
+        }
+
+        let source = match self.lookup(src) {
+            Some(source) => source,
+            None => return Ok(None),
+        };
+
+        let source_format = match SourceFormat::from_path(&source) {
+            Ok(format @ (SourceFormat::Png | SourceFormat::Jpeg | SourceFormat::WebP)) => format,
+            _ => return Ok(None),
+        };
+
+        let target_format = match self.config.format {
+            ImageFormat::Original => source_format,
+            ImageFormat::Webp => SourceFormat::WebP,
+            ImageFormat::Jpeg => SourceFormat::Jpeg,
+            ImageFormat::Png => SourceFormat::Png,
+        };
+
+        let bytes = fs::read(&source)?;
+
+        let mut hasher = DefaultHasher::new();
+        bytes.hash(&mut hasher);
+        self.config.hash(&mut hasher);
+        let key = hasher.finish();
+
+        let cache_path = |format: SourceFormat| {
+            self.cache_dir
+                .join(format!("{:016x}.{}", key, extension(format)))
+        };
+        let cached = match [target_format, source_format]
+            .into_iter()
+            .map(cache_path)
+            .find(|path| path.exists())
+        {
+            Some(cached) => cached,
+            None => {
+                let (encoded, format) = self.encode(&bytes, source_format, target_format)?;
+                let cached = cache_path(format);
+                fs::create_dir_all(&self.cache_dir)?;
+                fs::write(&cached, encoded)?;
+                cached
+            }
+        };
+        let extension = cached
+            .extension()
+            .and_then(|extension| extension.to_str())
+            .unwrap_or_default();
+
+        let (width, height) = image::image_dimensions(&cached)?;
+
+        let stem = source
+            .file_stem()
+            .and_then(|stem| stem.to_str())
+            .ok_or_else(|| anyhow!("Invalid image file name {:?}", source))?;
+        let filename = format!("{}-{:08x}.{}", stem, key as u32, extension);
+        let output = self.output_dir.join(&filename);
+
+        if !output.exists() {
+            fs::create_dir_all(&self.output_dir)?;
+            fs::copy(&cached, &output)?;
+        }
+        self.written.insert(filename.clone());
+
+        Ok(Some(ProcessedImage {
+            src: format!("{}images/{}", self.base_url, filename),
+            width,
+            height,
+        }))
+    }
+
+    fn lookup(&self, src: &str) -> Option<PathBuf> {
+        let relative = Path::new(src.trim_start_matches('/'));
+        self.source_dirs
+            .iter()
+            .map(|dir| dir.join(relative))
+            .find(|path| path.is_file())
+    }
+
+    /// Encoded image and its format, the source format when converting to WebP does not
+    /// make it smaller
+    fn encode(
+        &self,
+        bytes: &[u8],
+        source_format: SourceFormat,
+        target_format: SourceFormat,
+    ) -> Result<(Vec<u8>, SourceFormat)> {
+        let image = image::load_from_memory_with_format(bytes, source_format)?;
+        let needs_resize = matches!(self.config.max_width, Some(max) if image.width() > max);
+
+        // Nothing to do, keep the original bytes to avoid a lossy re-encoding
+        if !needs_resize && source_format == target_format {
+            return Ok((bytes.to_vec(), source_format));
+        }
+
+        let image = match self.config.max_width {
+            Some(max_width) if needs_resize => {
+                image.resize(max_width, u32::MAX, FilterType::Lanczos3)
+            }
+            _ => image,
+        };
+
+        let encoded = self.encode_image(&image, target_format)?;
+
+        // WebP is encoded losslessly, photos are often smaller in their original format
+        if target_format == SourceFormat::WebP && source_format != SourceFormat::WebP {
+            let original = if needs_resize {
+                self.encode_image(&image, source_format)?
+            } else {
+                bytes.to_vec()
+            };
+            if original.len() < encoded.len() {
+                return Ok((original, source_format));
+            }
+        }
+
+        Ok((encoded, target_format))
+    }
+
+    fn encode_image(&self, image: &DynamicImage, format: SourceFormat) -> Result<Vec<u8>> {
+        let mut encoded = vec![];
+        let result = match format {
+            SourceFormat::Jpeg => DynamicImage::ImageRgb8(image.to_rgb8()).write_with_encoder(
+                JpegEncoder::new_with_quality(&mut encoded, self.config.quality),
+            ),
+            SourceFormat::WebP if image.color().has_alpha() => {
+                DynamicImage::ImageRgba8(image.to_rgba8())
+                    .write_with_encoder(WebPEncoder::new_lossless(&mut encoded))
+            }
+            SourceFormat::WebP => DynamicImage::ImageRgb8(image.to_rgb8())
+                .write_with_encoder(WebPEncoder::new_lossless(&mut encoded)),
+            _ => image.write_with_encoder(PngEncoder::new(&mut encoded)),
+        };
+
+        result.map_err(|err| anyhow!("Could not encode image : {}", err))?;
+        Ok(encoded)
+    }
+}
+
+fn extension(format: SourceFormat) -> &'static str {
+    format.extensions_str()[0]
+}
+
+/// Remove the images processed by the previous build that are no longer written
+pub fn prune(written: &BTreeSet<String>) -> Result<()> {
+    prune_dir(Path::new(CACHE_DIR), Path::new(OUTPUT_DIR), written)
+}
+
+/// Only the files recorded in the cache as written by the processor are removed,
+/// the other files of `output_dir` belong to the user
+fn prune_dir(cache_dir: &Path, output_dir: &Path, written: &BTreeSet<String>) -> Result<()> {
+    let list = cache_dir.join(WRITTEN_LIST);
+    let previous = fs::read_to_string(&list).unwrap_or_default();
+
+    for filename in previous
+        .lines()
+        .filter(|filename| !written.contains(*filename))
+    {
+        let path = output_dir.join(filename);
+        if path.is_file() {
+            fs::remove_file(&path)?;
+        }
+    }
+
+    fs::create_dir_all(cache_dir)?;
+    let written: Vec<&str> = written.iter().map(String::as_str).collect();
+    fs::write(&list, written.join("\n"))?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        config::{ImageConfig, ImageFormat},
+        images::{prune_dir, ImageProcessor},
+    };
+    use image::{DynamicImage, ImageFormat as SourceFormat, Rgb, RgbImage};
+    use std::{fs, path::PathBuf};
+    use tempfile::TempDir;
+
+    fn processor(dir: &TempDir, format: ImageFormat) -> ImageProcessor {
+        ImageProcessor {
+            config: ImageConfig {
+                max_width: Some(10),
+                format,
+                quality: 80,
+            },
+            base_url: String::new(),
+            source_dirs: vec![dir.path().to_path_buf()],
+            cache_dir: dir.path().join("cache"),
+            output_dir: dir.path().join("public/images"),
+            written: Default::default(),
+        }
+    }
+
+    #[test]
+    fn should_resize_and_add_image_attributes() {
+        let dir = tempfile::tempdir().unwrap();
+        DynamicImage::new_rgb8(40, 20)
+            .save_with_format(dir.path().join("photo.png"), SourceFormat::Png)
+            .unwrap();
+
+        let output = processor(&dir, ImageFormat::Webp)
+            .rewrite_html(r#"<p><img src="photo.png" alt="photo" /></p>"#);
+
+        assert!(output.contains(r#"width="10" height="5" loading="lazy" alt="photo""#));
+
+        let src = output.split('"').nth(1).unwrap();
+        assert!(src.starts_with("images/photo-") && src.ends_with(".webp"));
+        assert!(dir.path().join("public").join(PathBuf::from(src)).exists());
+    }
+
+    #[test]
+    fn should_leave_remote_and_missing_images_untouched() {
+        let dir = tempfile::tempdir().unwrap();
+        let input =
+            r#"<img src="https://example.org/a.png" alt="" /><img src="missing.png" alt="" />"#;
+
+        let output = processor(&dir, ImageFormat::Original).rewrite_html(input);
+
+        assert_eq!(output, input);
+    }
+
+    #[test]
+    fn should_keep_photos_smaller_than_their_webp_version() {
+        let dir = tempfile::tempdir().unwrap();
+        // Noise compresses badly without loss
+        let mut seed = 1u32;
+        let noise = RgbImage::from_fn(64, 64, |_, _| {
+            seed = seed.wrapping_mul(1_103_515_245).wrapping_add(12345);
+            let [r, g, b, _] = seed.to_be_bytes();
+            Rgb([r, g, b])
+        });
+        DynamicImage::ImageRgb8(noise)
+            .save_with_format(dir.path().join("photo.jpg"), SourceFormat::Jpeg)
+            .unwrap();
+
+        let mut processor = processor(&dir, ImageFormat::Webp);
+        processor.config.max_width = None;
+        let output = processor.rewrite_html(r#"<img src="photo.jpg" alt="" />"#);
+
+        let src = output.split('"').nth(1).unwrap();
+        assert!(src.ends_with(".jpg"), "{}", src);
+    }
+
+    #[test]
+    fn should_only_prune_images_written_by_previous_builds() {
+        let dir = tempfile::tempdir().unwrap();
+        let output_dir = dir.path().join("public/images");
+        fs::create_dir_all(&output_dir).unwrap();
+        DynamicImage::new_rgb8(40, 20)
+            .save_with_format(output_dir.join("diagram-20240101.png"), SourceFormat::Png)
+            .unwrap();
+        fs::write(output_dir.join("photo-0badcafe.webp"), b"stale").unwrap();
+        fs::write(output_dir.join("logo-deadbeef.png"), b"user image").unwrap();
+        fs::create_dir_all(dir.path().join("cache")).unwrap();
+        fs::write(dir.path().join("cache/written"), "photo-0badcafe.webp").unwrap();
+
+        let mut processor = processor(&dir, ImageFormat::Webp);
+        let output =
+            processor.rewrite_html(r#"<img src="public/images/diagram-20240101.png" alt="" />"#);
+        prune_dir(&dir.path().join("cache"), &output_dir, processor.written()).unwrap();
+
+        let src = output.split('"').nth(1).unwrap();
+        assert!(dir.path().join("public").join(src).exists());
+        assert!(output_dir.join("diagram-20240101.png").exists());
+        assert!(output_dir.join("logo-deadbeef.png").exists());
+        assert!(!output_dir.join("photo-0badcafe.webp").exists());
+    }
+}
@@ -0,0 +1,715 @@
+use crate::document::MD_CLASS_RE;
+use anyhow::{anyhow, Error, Result};
+use once_cell::sync::Lazy;
+use regex::Regex;
+use std::{ops::Range, str::FromStr};
+
+static COMMENT_RE: Lazy<Regex> = Lazy::new(|| Regex::new(r"(?s)<!--(.*?)-->").unwrap());
+static DIRECTIVE_RE: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"^\s*(_?)([A-Za-z]+)\s*:\s*(.*?)\s*$").unwrap());
+static IMAGE_LINE_RE: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"^\s*!\[([^\]]*)\]\(([^)\s]+)[^)]*\)\s*$").unwrap());
+static DECKSET_COMMAND_RE: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"^\s*\[\.([a-z-]+)(?::\s*(.*?))?\]\s*$").unwrap());
+static DECKSET_GLOBAL_RE: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"^([a-z-]+):\s*(.*?)\s*$").unwrap());
+static REVEAL_SLIDE_RE: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"(?s)<!--\s*\.slide:(.*?)-->").unwrap());
+static REVEAL_ELEMENT_RE: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"(?s)<!--\s*\.element:(.*?)-->").unwrap());
+static REVEAL_NOTES_RE: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"(?m)^\s*Notes?:[[:blank:]]*").unwrap());
+static ATTRIBUTE_RE: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r#"([A-Za-z-]+)\s*=\s*"([^"]*)""#).unwrap());
+
+// Marp directives, only the ones with a css equivalent are converted
+const MARP_DIRECTIVES: &[&str] = &[
+    "marp",
+    "theme",
+    "style",
+    "headingDivider",
+    "size",
+    "math",
+    "title",
+    "author",
+    "description",
+    "image",
+    "url",
+    "lang",
+    "paginate",
+    "header",
+    "footer",
+    "class",
+    "backgroundColor",
+    "backgroundImage",
+    "backgroundPosition",
+    "backgroundRepeat",
+    "backgroundSize",
+    "color",
+];
+
+/// Markdown presentation formats supported by `unveil import`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImportFormat {
+    Marp,
+    Deckset,
+    RevealJs,
+}
+
+impl FromStr for ImportFormat {
+    type Err = Error;
+
+    fn from_str(format: &str) -> Result<Self> {
+        match format {
+            "marp" => Ok(ImportFormat::Marp),
+            "deckset" => Ok(ImportFormat::Deckset),
+            "revealjs" => Ok(ImportFormat::RevealJs),
+            other => Err(anyhow!("Unknown import format {}", other)),
+        }
+    }
+}
+
+/// A converted slide and the file name it should be written to
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ImportedSlide {
+    pub filename: String,
+    pub markdown: String,
+}
+
+/// Slide properties converted to the slide stylematter
+#[derive(Debug, Clone, Default)]
+struct SlideStyle {
+    background_color: Option<String>,
+    background_image: Option<String>,
+    background_size: Option<String>,
+    background_position: Option<String>,
+    color: Option<String>,
+    class: Option<String>,
+}
+
+struct ConvertedSlide {
+    content: String,
+    notes: Vec<String>,
+    style: SlideStyle,
+}
+
+/// Split a presentation written for another tool into unveil slides
+pub fn import(source: &str, format: ImportFormat) -> Vec<ImportedSlide> {
+    let slides = match format {
+        ImportFormat::Marp => convert_marp(source),
+        ImportFormat::Deckset => convert_deckset(source),
+        ImportFormat::RevealJs => convert_revealjs(source),
+    };
+
+    slides
+        .into_iter()
+        .filter(|slide| !slide.content.trim().is_empty() || !slide.notes.is_empty())
+        .enumerate()
+        .map(|(idx, slide)| slide.render(idx + 1))
+        .collect()
+}
+
+fn convert_marp(source: &str) -> Vec<ConvertedSlide> {
+    let mut global = SlideStyle::default();
+
+    // The front matter holds the global directives
+    let body = match split_front_matter(source) {
+        Some((front_matter, body)) => {
+            for line in front_matter.lines() {
+                if let Some(caps) = DIRECTIVE_RE.captures(line) {
+                    global.apply_marp(&caps[2], &caps[3]);
+                }
+            }
+            body
+        }
+        None => source,
+    };
+
+    split_slides(body, &["---", "***", "___"])
+        .into_iter()
+        .map(|chunk| {
+            let mut style = global.clone();
+            let mut notes = vec![];
+            let mut local = SlideStyle::default();
+            let code = code_ranges(&chunk);
+
+            let content = COMMENT_RE.replace_all(&chunk, |caps: &regex::Captures| {
+                // Comments in code samples are part of the code
+                if in_code(&code, caps.get(0).unwrap().start()) {
+                    return caps[0].to_string();
+                }
+
+                let comment = caps[1].trim();
+                let directives: Option<Vec<(bool, String, String)>> = comment
+                    .lines()
+                    .filter(|line| !line.trim().is_empty())
+                    .map(|line| {
+                        DIRECTIVE_RE
+                            .captures(line)
+                            .filter(|caps| MARP_DIRECTIVES.contains(&&caps[2]))
+                            .map(|caps| {
+                                (
+                                    !caps[1].is_empty(),
+                                    caps[2].to_string(),
+                                    caps[3].to_string(),
+                                )
+                            })
+                    })
+                    .collect();
+
+                match directives {
+                    Some(directives) if !directives.is_empty() => {
+                        for (spot, key, value) in directives {
+                            // Spot directives `_key` only apply to the current slide
+                            if spot {
+                                local.apply_marp(&key, &value);
+                            } else {
+                                global.apply_marp(&key, &value);
+                                style.apply_marp(&key, &value);
+                            }
+                        }
+                    }
+                    _ if !comment.is_empty() => notes.push(comment.to_string()),
+                    _ => {}
+                }
+
+                String::new()
+            });
+
+            style.merge(local);
+
+            // `![bg](image.png)` is a background image
+            let mut fences = Fences::default();
+            let content = content
+                .lines()
+                .filter(|line| match IMAGE_LINE_RE.captures(line) {
+                    _ if fences.in_code(line) => true,
+                    Some(caps) if caps[1].split_whitespace().next() == Some("bg") => {
+                        style.background_image = Some(caps[2].to_string());
+                        style.background_size = Some(
+                            if caps[1].contains("contain") || caps[1].contains("fit") {
+                                "contain"
+                            } else {
+                                "cover"
+                            }
+                            .to_string(),
+                        );
+                        false
+                    }
+                    _ => true,
+                })
+                .collect::<Vec<&str>>()
+                .join("\n");
+
+            ConvertedSlide {
+                content,
+                notes,
+                style,
+            }
+        })
+        .collect()
+}
+
+fn convert_deckset(source: &str) -> Vec<ConvertedSlide> {
+    let mut global = SlideStyle::default();
+
+    // Global commands are the `key: value` lines at the top of the file
+    let mut offset = 0;
+    for line in source.split_inclusive('\n') {
+        match DECKSET_GLOBAL_RE.captures(line.trim_end()) {
+            Some(caps) => global.apply_deckset(&caps[1], Some(&caps[2])),
+            None => break,
+        }
+        offset += line.len();
+    }
+    let body = &source[offset..];
+
+    split_slides(body, &["---"])
+        .into_iter()
+        .map(|chunk| {
+            let mut style = global.clone();
+            let mut notes = vec![];
+            let mut content = vec![];
+            let mut fences = Fences::default();
+
+            for line in chunk.lines() {
+                if fences.in_code(line) {
+                    content.push(line);
+                } else if let Some(note) = line.strip_prefix('^') {
+                    notes.push(note.trim().to_string());
+                } else if let Some(caps) = DECKSET_COMMAND_RE.captures(line) {
+                    style.apply_deckset(&caps[1], caps.get(2).map(|value| value.as_str()));
+                } else if let Some(caps) = IMAGE_LINE_RE
+                    .captures(line)
+                    .filter(|caps| is_deckset_background(&caps[1]))
+                {
+                    style.background_image = Some(caps[2].to_string());
+                    style.background_size = Some(
+                        if caps[1].contains("fit") {
+                            "contain"
+                        } else {
+                            "cover"
+                        }
+                        .to_string(),
+                    );
+                } else {
+                    content.push(line);
+                }
+            }
+
+            ConvertedSlide {
+                content: content.join("\n"),
+                notes,
+                style,
+            }
+        })
+        .collect()
+}
+
+/// Deckset displays images alone on their line as backgrounds unless told otherwise
+fn is_deckset_background(modifiers: &str) -> bool {
+    modifiers
+        .split_whitespace()
+        .all(|modifier| matches!(modifier, "fit" | "filtered" | "original" | "fill"))
+}
+
+fn convert_revealjs(source: &str) -> Vec<ConvertedSlide> {
+    // Vertical slides are flattened after their parent
+    split_slides(source, &["---", "--"])
+        .into_iter()
+        .map(|chunk| {
+            let code = code_ranges(&chunk);
+            let (content, notes) = match REVEAL_NOTES_RE
+                .find_iter(&chunk)
+                .find(|separator| !in_code(&code, separator.start()))
+            {
+                Some(separator) => (
+                    chunk[..separator.start()].to_string(),
+                    vec![chunk[separator.end()..].trim().to_string()],
+                ),
+                None => (chunk, vec![]),
+            };
+
+            let mut style = SlideStyle::default();
+            let content = REVEAL_SLIDE_RE.replace_all(&content, |caps: &regex::Captures| {
+                if in_code(&code, caps.get(0).unwrap().start()) {
+                    return caps[0].to_string();
+                }
+                for attribute in ATTRIBUTE_RE.captures_iter(&caps[1]) {
+                    style.apply_revealjs(&attribute[1], &attribute[2]);
+                }
+                String::new()
+            });
+
+            // Element attributes become the unveil class extension
+            let code = code_ranges(&content);
+            let content = REVEAL_ELEMENT_RE.replace_all(&content, |caps: &regex::Captures| {
+                if in_code(&code, caps.get(0).unwrap().start()) {
+                    return caps[0].to_string();
+                }
+                ATTRIBUTE_RE
+                    .captures_iter(&caps[1])
+                    .find(|attribute| &attribute[1] == "class")
+                    .map(|attribute| format!(r#"[class="{}"]"#, &attribute[2]))
+                    .unwrap_or_default()
+            });
+
+            ConvertedSlide {
+                content: content.to_string(),
+                notes: notes.into_iter().filter(|note| !note.is_empty()).collect(),
+                style,
+            }
+        })
+        .collect()
+}
+
+/// Split the presentation on separator lines, ignoring the ones inside code blocks
+fn split_slides(source: &str, separators: &[&str]) -> Vec<String> {
+    let mut slides = vec![];
+    let mut current = String::new();
+    let mut fences = Fences::default();
+
+    for line in source.lines() {
+        let trimmed = line.trim();
+
+        if !fences.in_code(line) && separators.contains(&trimmed) && line.starts_with(trimmed) {
+            slides.push(std::mem::take(&mut current));
+            continue;
+        }
+
+        current.push_str(line);
+        current.push('\n');
+    }
+
+    slides.push(current);
+    slides
+}
+
+/// Fenced code blocks, followed line by line
+#[derive(Default)]
+struct Fences {
+    marker: Option<&'static str>,
+}
+
+impl Fences {
+    /// Whether the line belongs to a code block, its fences included
+    fn in_code(&mut self, line: &str) -> bool {
+        let trimmed = line.trim();
+        match self.marker {
+            Some(marker) => {
+                if trimmed.starts_with(marker) {
+                    self.marker = None;
+                }
+                true
+            }
+            None => {
+                self.marker = ["```", "~~~"]
+                    .into_iter()
+                    .find(|marker| trimmed.starts_with(marker));
+                self.marker.is_some()
+            }
+        }
+    }
+}
+
+/// Byte ranges of the code block lines of `text`
+fn code_ranges(text: &str) -> Vec<Range<usize>> {
+    let mut fences = Fences::default();
+    let mut ranges = vec![];
+    let mut offset = 0;
+    for line in text.split_inclusive('\n') {
+        if fences.in_code(line) {
+            ranges.push(offset..offset + line.len());
+        }
+        offset += line.len();
+    }
+
+    ranges
+}
+
+fn in_code(ranges: &[Range<usize>], position: usize) -> bool {
+    ranges.iter().any(|range| range.contains(&position))
+}
+
+/// Split a leading yaml front matter delimited by `---` lines
+fn split_front_matter(source: &str) -> Option<(&str, &str)> {
+    let rest = source
+        .strip_prefix("---\n")
+        .or_else(|| source.strip_prefix("---\r\n"))?;
+
+    let mut offset = 0;
+    for line in rest.split_inclusive('\n') {
+        if line.trim_end() == "---" {
+            return Some((&rest[..offset], &rest[offset + line.len()..]));
+        }
+        offset += line.len();
+    }
+
+    None
+}
+
+impl SlideStyle {
+    fn apply_marp(&mut self, key: &str, value: &str) {
+        let value = unquote(value);
+        match key {
+            "class" => self.class = Some(value),
+            "backgroundColor" => self.background_color = Some(value),
+            "backgroundImage" => self.background_image = Some(value),
+            "backgroundSize" => self.background_size = Some(value),
+            "backgroundPosition" => self.background_position = Some(value),
+            "color" => self.color = Some(value),
+            _ => {}
+        }
+    }
+
+    fn apply_deckset(&mut self, key: &str, value: Option<&str>) {
+        let value = match value {
+            Some(value) => value,
+            None => return,
+        };
+
+        match key {
+            "background-color" => self.background_color = Some(unquote(value)),
+            // `[.text: #ffffff, alignment(center)]`, only the color is kept
+            "text" => {
+                self.color = value
+                    .split(',')
+                    .map(str::trim)
+                    .find(|part| part.starts_with('#'))
+                    .map(str::to_string)
+            }
+            _ => {}
+        }
+    }
+
+    fn apply_revealjs(&mut self, attribute: &str, value: &str) {
+        match attribute {
+            "class" => self.class = Some(value.to_string()),
+            "data-background-color" => self.background_color = Some(value.to_string()),
+            "data-background-image" => self.background_image = Some(value.to_string()),
+            "data-background-size" => self.background_size = Some(value.to_string()),
+            "data-background-position" => self.background_position = Some(value.to_string()),
+            // `data-background` is either a color or an image
+            "data-background" if value.contains('.') || value.contains('/') => {
+                self.background_image = Some(value.to_string());
+                self.background_size = Some("cover".to_string());
+            }
+            "data-background" => self.background_color = Some(value.to_string()),
+            _ => {}
+        }
+    }
+
+    fn merge(&mut self, other: SlideStyle) {
+        self.background_color = other.background_color.or(self.background_color.take());
+        self.background_image = other.background_image.or(self.background_image.take());
+        self.background_size = other.background_size.or(self.background_size.take());
+        self.background_position = other
+            .background_position
+            .or(self.background_position.take());
+        self.color = other.color.or(self.color.take());
+        self.class = other.class.or(self.class.take());
+    }
+
+    fn to_stylematter(&self) -> Option<String> {
+        let mut stylematter = String::new();
+
+        if let Some(color) = &self.background_color {
+            stylematter.push_str(&format!("background-color: {};\n", color));
+        }
+
+        if let Some(image) = &self.background_image {
+            let image = image
+                .trim_start_matches("url(")
+                .trim_end_matches(')')
+                .trim_matches(|char| char == '"' || char == '\'');
+            stylematter.push_str(&format!("background-image: url(\"{}\");\n", image));
+            stylematter.push_str(&format!(
+                "background-size: {};\n",
+                self.background_size.as_deref().unwrap_or("cover")
+            ));
+            stylematter.push_str("background-repeat: no-repeat;\n");
+        }
+
+        if let Some(position) = &self.background_position {
+            stylematter.push_str(&format!("background-position: {};\n", position));
+        }
+
+        if let Some(color) = &self.color {
+            stylematter.push_str(&format!("color: {};\n", color));
+        }
+
+        if stylematter.is_empty() {
+            None
+        } else {
+            Some(stylematter)
+        }
+    }
+}
+
+impl ConvertedSlide {
+    fn render(self, number: usize) -> ImportedSlide {
+        let mut markdown = String::new();
+
+        if let Some(stylematter) = self.style.to_stylematter() {
+            markdown.push_str(&format!("+++\n{}+++\n", stylematter));
+        }
+
+        let mut content = self.content.trim().to_string();
+        let filename = format!("{:02}_{}.md", number, slug(&content));
+
+        if let Some(class) = &self.style.class {
+            content = add_class(&content, class);
+        }
+
+        markdown.push_str(&content);
+        markdown.push('\n');
+
+        if !self.notes.is_empty() {
+            markdown.push_str("???\n");
+            markdown.push_str(&self.notes.join("\n\n"));
+            markdown.push('\n');
+        }
+
+        ImportedSlide { filename, markdown }
+    }
+}
+
+/// Attach the slide class to its first heading, or to its first paragraph
+fn add_class(content: &str, class: &str) -> String {
+    let lines: Vec<&str> = content.lines().collect();
+    let target = lines
+        .iter()
+        .position(|line| line.starts_with('#'))
+        .or_else(|| {
+            lines.iter().position(|line| {
+                let line = line.trim();
+                !line.is_empty()
+                    && !line.starts_with("```")
+                    && !line.starts_with("~~~")
+                    && !line.starts_with('<')
+                    && !IMAGE_LINE_RE.is_match(line)
+            })
+        });
+
+    match target {
+        Some(target) => lines
+            .iter()
+            .enumerate()
+            .map(|(idx, line)| {
+                if idx == target {
+                    format!(r#"{} [class="{}"]"#, line, class)
+                } else {
+                    line.to_string()
+                }
+            })
+            .collect::<Vec<String>>()
+            .join("\n"),
+        None => content.to_string(),
+    }
+}
+
+/// File name friendly version of the slide first heading
+fn slug(content: &str) -> String {
+    let heading = content
+        .lines()
+        .find(|line| line.starts_with('#'))
+        .map(|line| MD_CLASS_RE.replace_all(line.trim_start_matches('#'), ""))
+        .unwrap_or_default();
+
+    let slug = heading
+        .to_lowercase()
+        .split(|char: char| !char.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .take(5)
+        .collect::<Vec<&str>>()
+        .join("_");
+
+    if slug.is_empty() {
+        "slide".to_string()
+    } else {
+        slug
+    }
+}
+
+fn unquote(value: &str) -> String {
+    value
+        .trim()
+        .trim_matches(|char| char == '"' || char == '\'')
+        .to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::import::{import, ImportFormat, ImportedSlide};
+
+    fn slide(filename: &str, markdown: &str) -> ImportedSlide {
+        ImportedSlide {
+            filename: filename.into(),
+            markdown: markdown.into(),
+        }
+    }
+
+    #[test]
+    fn should_import_marp_directives() {
+        let source = r#"---
+marp: true
+theme: gaia
+backgroundColor: black
+---
+
+<!-- _class: lead -->
+
+# Hello Marp
+
+<!-- Greet the audience -->
+
+---
+
+<!-- color: white -->
+![bg contain](images/ferris.png)
+
+```yaml
+---
+key: value
+```
+"#;
+
+        assert_eq!(
+            import(source, ImportFormat::Marp),
+            vec![
+                slide(
+                    "01_hello_marp.md",
+                    "+++\nbackground-color: black;\n+++\n# Hello Marp [class=\"lead\"]\n???\nGreet the audience\n"
+                ),
+                slide(
+                    "02_slide.md",
+                    "+++\nbackground-color: black;\nbackground-image: url(\"images/ferris.png\");\nbackground-size: contain;\nbackground-repeat: no-repeat;\ncolor: white;\n+++\n```yaml\n---\nkey: value\n```\n"
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn should_import_deckset_commands() {
+        let source = "theme: Next\nslidenumbers: true\n\n# Deckset\n\n^ A note\n\n---\n\n[.background-color: #FF0000]\n[.text: #FFFFFF, alignment(center)]\n\n- red\n";
+
+        assert_eq!(
+            import(source, ImportFormat::Deckset),
+            vec![
+                slide("01_deckset.md", "# Deckset\n???\nA note\n"),
+                slide(
+                    "02_slide.md",
+                    "+++\nbackground-color: #FF0000;\ncolor: #FFFFFF;\n+++\n- red\n"
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn should_import_revealjs_attributes() {
+        let source = "<!-- .slide: data-background=\"#ff0000\" class=\"title\" -->\n# Reveal\n\n--\n\n- item <!-- .element: class=\"fragment\" -->\n\nNote: Remember this\n";
+
+        assert_eq!(
+            import(source, ImportFormat::RevealJs),
+            vec![
+                slide(
+                    "01_reveal.md",
+                    "+++\nbackground-color: #ff0000;\n+++\n# Reveal [class=\"title\"]\n"
+                ),
+                slide(
+                    "02_slide.md",
+                    "- item [class=\"fragment\"]\n???\nRemember this\n"
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn should_keep_comments_and_notes_inside_code_blocks() {
+        let marp = "# Html\n\n```html\n<!-- keep me -->\n![bg](kept.png)\n```\n\n<!-- A note -->\n";
+        let deckset = "# Deckset\n\n```rust\n^ kept\n```\n\n^ A note\n";
+        let revealjs = "# Reveal\n\n```\nNote: kept\n```\n\nNote: A note\n";
+
+        assert_eq!(
+            import(marp, ImportFormat::Marp),
+            vec![slide(
+                "01_html.md",
+                "# Html\n\n```html\n<!-- keep me -->\n![bg](kept.png)\n```\n???\nA note\n"
+            )]
+        );
+        assert_eq!(
+            import(deckset, ImportFormat::Deckset),
+            vec![slide(
+                "01_deckset.md",
+                "# Deckset\n\n```rust\n^ kept\n```\n???\nA note\n"
+            )]
+        );
+        assert_eq!(
+            import(revealjs, ImportFormat::RevealJs),
+            vec![slide(
+                "01_reveal.md",
+                "# Reveal\n\n```\nNote: kept\n```\n???\nA note\n"
+            )]
+        );
+    }
+}
@@ -1,6 +1,12 @@
 pub mod assets;
 pub mod config;
+pub mod document;
+pub mod export;
 pub mod helper;
 pub mod html;
+pub mod images;
+pub mod import;
+pub mod manifest;
+pub mod present;
 pub mod server;
 pub mod unveil;
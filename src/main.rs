@@ -1,5 +1,10 @@
-use clap::{App, AppSettings, Arg, SubCommand};
-use unveil_rs::{server::Server, unveil::UnveilProject};
+use clap::{App, AppSettings, Arg, ArgMatches, SubCommand};
+use unveil_rs::{
+    export::ExportFormat,
+    import::ImportFormat,
+    server::{Server, ServerPort, Tls},
+    unveil::UnveilProject,
+};
 
 #[tokio::main]
 async fn main() {
@@ -23,36 +28,86 @@ async fn main() {
                 ),
             SubCommand::with_name("build")
                 .display_order(2)
-                .about("Build the project static site files in `public` directory"),
+                .about("Build the project static site files in `public` directory")
+                .args(&[
+                    Arg::with_name("release").long("release").help(
+                        "Build for production: no live-reload, minified and unused assets skipped",
+                    ),
+                    Arg::with_name("emit-json")
+                        .long("emit-json")
+                        .help("Write a `manifest.json` file describing each slide"),
+                ]),
             SubCommand::with_name("serve")
                 .display_order(3)
                 .about("Serve your project with live-reload")
+                .args(&server_args()),
+            SubCommand::with_name("preview")
+                .display_order(4)
+                .about(
+                    "Build the project for production and serve it with production cache headers",
+                )
+                .args(&server_args()),
+            SubCommand::with_name("add")
+                .display_order(5)
+                .about("Add a markdown slide to the presentation")
                 .args(&[
-                    Arg::with_name("host")
-                        .required(false)
-                        .long("hostname")
-                        .short("h")
+                    Arg::with_name("SLIDE_NAME").required(true),
+                    Arg::with_name("deck")
+                        .long("deck")
+                        .short("d")
                         .takes_value(true)
-                        .help("Hostname to serve on, default `localhost`"),
-                    Arg::with_name("http-port")
-                        .required(false)
-                        .long("http-port")
-                        .short("p")
+                        .help("Add the slide to the given deck of a multi-deck project"),
+                ]),
+            SubCommand::with_name("export")
+                .display_order(6)
+                .about("Export the presentation to another document format")
+                .args(&[
+                    Arg::with_name("format")
+                        .required(true)
+                        .long("format")
+                        .short("f")
                         .takes_value(true)
-                        .help("Http port to serve on, default `7878`"),
-                    Arg::with_name("ws-port")
-                        .required(false)
-                        .long("ws-port")
-                        .short("w")
+                        .possible_values(&["pptx", "odp", "handout", "epub"])
+                        .help("Format of the exported document"),
+                    Arg::with_name("deck")
+                        .long("deck")
+                        .short("d")
                         .takes_value(true)
-                        .help("Web socket port used for live-reload, default `3000`"),
+                        .help("Only export the given deck of a multi-deck project"),
+                    Arg::with_name("output")
+                        .long("output")
+                        .short("o")
+                        .takes_value(true)
+                        .help("Output file, default to the project or deck name"),
+                ]),
+            SubCommand::with_name("import")
+                .display_order(7)
+                .about("Import a Marp, Deckset or reveal.js presentation into `slides`")
+                .args(&[
+                    Arg::with_name("FILE").required(true),
+                    Arg::with_name("from")
+                        .required(true)
+                        .long("from")
+                        .takes_value(true)
+                        .possible_values(&["marp", "deckset", "revealjs"])
+                        .help("Tool the presentation was written for"),
+                ]),
+            SubCommand::with_name("present")
+                .display_order(8)
+                .about("Present the slides directly in the terminal")
+                .args(&[
+                    Arg::with_name("tty")
+                        .required(true)
+                        .long("tty")
+                        .help("Render the slides in the terminal, navigate with the arrow keys"),
+                    Arg::with_name("deck")
+                        .long("deck")
+                        .short("d")
+                        .takes_value(true)
+                        .help("Deck to present in a multi-deck project"),
                 ]),
-            SubCommand::with_name("add")
-                .display_order(4)
-                .about("Add a markdown slide to the presentation")
-                .arg(Arg::with_name("SLIDE_NAME").required(true)),
             SubCommand::with_name("clean")
-                .display_order(5)
+                .display_order(9)
                 .about("Remove all static files and the `public` directory"),
         ])
         .get_matches();
@@ -66,31 +121,136 @@ async fn main() {
                 .value_of("PROJECT_NAME");
             project.init(project_name).unwrap();
         }
-        "build" => project.build(&Server::default()).unwrap(),
+        "build" => {
+            let build = matches.subcommand_matches("build").unwrap();
+            project.release = build.is_present("release");
+            project.emit_json = build.is_present("emit-json");
+            project.build().unwrap()
+        }
         "serve" => {
             let serve = matches.subcommand_matches("serve").unwrap();
-            let http_port = serve
-                .value_of("http-port")
-                .map(|value| value.parse::<i32>().expect("Error : invalid http port"));
-            let ws_port = serve.value_of("ws-port").map(|value| {
-                value
-                    .parse::<i32>()
-                    .expect("Error : invalid websocket port")
-            });
-            let hostname = serve.value_of("hostname");
-
-            project.serve(hostname, http_port, ws_port).await.unwrap()
+            if let Err(err) = project.serve(server(serve)).await {
+                exit(err)
+            }
+        }
+        "preview" => {
+            let preview = matches.subcommand_matches("preview").unwrap();
+            if let Err(err) = project.preview(server(preview)).await {
+                exit(err)
+            }
         }
         "add" => {
-            let slide_name = matches
-                .subcommand_matches("add")
+            let add = matches.subcommand_matches("add").unwrap();
+            let slide_name = add.value_of("SLIDE_NAME").unwrap();
+            let deck = add.value_of("deck");
+
+            project.new_slide(slide_name, deck).unwrap()
+        }
+        "export" => {
+            let export = matches.subcommand_matches("export").unwrap();
+            let format = export
+                .value_of("format")
+                .unwrap()
+                .parse::<ExportFormat>()
+                .unwrap();
+
+            project
+                .export(format, export.value_of("deck"), export.value_of("output"))
                 .unwrap()
-                .value_of("SLIDE_NAME")
+        }
+        "import" => {
+            let import = matches.subcommand_matches("import").unwrap();
+            let format = import
+                .value_of("from")
+                .unwrap()
+                .parse::<ImportFormat>()
                 .unwrap();
 
-            project.new_slide(slide_name).unwrap()
+            project
+                .import(import.value_of("FILE").unwrap(), format)
+                .unwrap()
+        }
+        "present" => {
+            let present = matches.subcommand_matches("present").unwrap();
+            project.present(present.value_of("deck")).unwrap()
         }
         "clean" => UnveilProject::clean().unwrap(),
         _ => (),
     }
 }
+
+/// Arguments shared by `serve` and `preview`
+fn server_args<'a, 'b>() -> Vec<Arg<'a, 'b>> {
+    vec![
+        Arg::with_name("host")
+            .required(false)
+            .long("hostname")
+            .short("h")
+            .takes_value(true)
+            .help("Hostname to serve on, default `localhost`"),
+        Arg::with_name("port")
+            .required(false)
+            .long("port")
+            .alias("http-port")
+            .short("p")
+            .takes_value(true)
+            .help("Http port to serve on, default `7878`, or `auto` to pick the first free one"),
+        Arg::with_name("no-open")
+            .long("no-open")
+            .help("Do not open the presentation in the browser"),
+        Arg::with_name("headless")
+            .long("headless")
+            .help("Never open the browser and print the url as a json line, for scripts and CI"),
+        Arg::with_name("https")
+            .long("https")
+            .help("Serve over https with a cached self-signed certificate"),
+        Arg::with_name("cert")
+            .long("cert")
+            .takes_value(true)
+            .requires("key")
+            .help("PEM certificate to serve over https instead of the self-signed one"),
+        Arg::with_name("key")
+            .long("key")
+            .takes_value(true)
+            .requires("cert")
+            .help("PEM private key of the `--cert` certificate"),
+        // Live reload moved to the http port, kept so existing scripts still run
+        Arg::with_name("ws-port")
+            .long("ws-port")
+            .short("w")
+            .takes_value(true)
+            .hidden(true),
+    ]
+}
+
+fn server(matches: &ArgMatches) -> Server {
+    if matches.is_present("ws-port") {
+        eprintln!(
+            "Warning : `--ws-port` is deprecated and ignored, live reload uses the http port"
+        );
+    }
+    let port = matches
+        .value_of("port")
+        .map(|value| value.parse::<ServerPort>().unwrap_or_else(|err| exit(err)));
+    let tls = match (matches.value_of("cert"), matches.value_of("key")) {
+        (Some(cert), Some(key)) => Some(Tls::Files {
+            cert: cert.into(),
+            key: key.into(),
+        }),
+        _ if matches.is_present("https") => Some(Tls::SelfSigned),
+        _ => None,
+    };
+
+    Server::default()
+        .with_hostname(matches.value_of("host"))
+        .with_port(port)
+        .with_tls(tls)
+        .with_open(!matches.is_present("no-open"))
+        .with_headless(matches.is_present("headless"))
+}
+
+/// Report the error without a panic backtrace
+fn exit(err: anyhow::Error) -> ! {
+    eprintln!("Error : {}", err);
+    std::process::exit(1)
+}
@@ -0,0 +1,143 @@
+use crate::{document::first_heading, html::Slide};
+use pulldown_cmark::{CodeBlockKind, Event, Options, Parser, Tag};
+use serde::Serialize;
+
+/// Machine readable description of a deck, written to `manifest.json`
+/// by `unveil build --emit-json`
+#[derive(Serialize, Debug)]
+pub struct Manifest {
+    pub title: String,
+    pub slides: Vec<SlideManifest>,
+}
+
+#[derive(Serialize, Debug, PartialEq, Eq)]
+pub struct SlideManifest {
+    /// Markdown file the slide was built from
+    pub source: String,
+    /// Id of the slide `<section>` in the generated html
+    pub id: String,
+    /// Text of the first heading
+    pub title: Option<String>,
+    pub notes: Option<String>,
+    /// Local images and files referenced by the slide
+    pub assets: Vec<String>,
+    pub code_blocks: Vec<CodeBlock>,
+}
+
+#[derive(Serialize, Debug, PartialEq, Eq)]
+pub struct CodeBlock {
+    pub language: Option<String>,
+    pub code: String,
+}
+
+impl Manifest {
+    /// Describe the slides from their source path and markdown content
+    pub fn new(title: &str, slides: &[(String, String)]) -> Manifest {
+        let slides = slides
+            .iter()
+            .enumerate()
+            .map(|(idx, (source, markdown))| SlideManifest::new(idx, source, markdown))
+            .collect();
+
+        Manifest {
+            title: title.to_owned(),
+            slides,
+        }
+    }
+}
+
+impl SlideManifest {
+    fn new(idx: usize, source: &str, markdown: &str) -> SlideManifest {
+        let slide = Slide::parse(markdown);
+        let title = first_heading(&slide.content).map(|(_, title)| title);
+        let mut assets = vec![];
+        let mut code_blocks = vec![];
+
+        let mut code_block: Option<CodeBlock> = None;
+
+        for event in Parser::new_ext(&slide.content, Options::empty()) {
+            match event {
+                Event::Start(Tag::CodeBlock(kind)) => {
+                    let language = match kind {
+                        CodeBlockKind::Fenced(info) => info
+                            .split_whitespace()
+                            .next()
+                            .map(|language| language.to_string()),
+                        CodeBlockKind::Indented => None,
+                    };
+
+                    code_block = Some(CodeBlock {
+                        language,
+                        code: String::new(),
+                    });
+                }
+                Event::End(Tag::CodeBlock(_)) => code_blocks.extend(code_block.take()),
+                Event::Start(Tag::Image(_, src, _)) | Event::Start(Tag::Link(_, src, _))
+                    if is_local(&src) =>
+                {
+                    assets.push(src.to_string())
+                }
+                Event::Text(text) => {
+                    if let Some(code_block) = code_block.as_mut() {
+                        code_block.code.push_str(&text);
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        SlideManifest {
+            source: source.to_owned(),
+            id: format!("unveil-slide-{}", idx),
+            title,
+            notes: slide.notes,
+            assets,
+            code_blocks,
+        }
+    }
+}
+
+fn is_local(url: &str) -> bool {
+    !(url.is_empty()
+        || url.contains("://")
+        || url.starts_with("//")
+        || url.starts_with('#')
+        || url.starts_with("mailto:")
+        || url.starts_with("data:"))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::manifest::{CodeBlock, Manifest, SlideManifest};
+
+    #[test]
+    fn should_describe_slides() {
+        let markdown = r#"# Hello `world` [class="red"]
+
+![ferris](img/ferris.png) [docs](https://docs.rs)
+
+```rust
+fn main() {}
+```
+???
+Greet the audience
+"#;
+
+        let manifest = Manifest::new("demo", &[("slides/hello.md".into(), markdown.into())]);
+
+        assert_eq!(
+            manifest.slides,
+            vec![SlideManifest {
+                source: "slides/hello.md".into(),
+                id: "unveil-slide-0".into(),
+                title: Some("Hello world".into()),
+                notes: Some("Greet the audience".into()),
+                assets: vec!["img/ferris.png".into()],
+                code_blocks: vec![CodeBlock {
+                    language: Some("rust".into()),
+                    code: "fn main() {}\n".into()
+                }],
+            }]
+        );
+    }
+}
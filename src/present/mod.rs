@@ -0,0 +1,153 @@
+use crate::document::Document;
+use anyhow::{anyhow, Result};
+use crossterm::{
+    cursor,
+    event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers},
+    execute, queue,
+    style::{Attribute, Color, Print, SetAttribute, SetForegroundColor},
+    terminal::{self, Clear, ClearType, EnterAlternateScreen, LeaveAlternateScreen},
+};
+use std::io::{self, IsTerminal, Stdout, Write};
+
+mod render;
+
+use render::{Renderer, StyledText};
+
+// Blank columns on each side of the slide
+const MARGIN: u16 = 4;
+
+/// Present the document in the terminal until the user quits
+pub fn present(document: &Document) -> Result<()> {
+    if document.slides.is_empty() {
+        return Err(anyhow!("No slides to present"));
+    }
+
+    if !io::stdout().is_terminal() {
+        return Err(anyhow!("unveil present --tty must be run in a terminal"));
+    }
+
+    let renderer = Renderer::default();
+    let last = document.slides.len() - 1;
+    let mut current = 0;
+
+    let mut stdout = io::stdout();
+    let _terminal = RawTerminal::enter()?;
+
+    loop {
+        draw(&mut stdout, &renderer, document, current)?;
+
+        if let Event::Key(KeyEvent {
+            code,
+            modifiers,
+            kind: KeyEventKind::Press,
+            ..
+        }) = event::read()?
+        {
+            match code {
+                KeyCode::Right
+                | KeyCode::Down
+                | KeyCode::PageDown
+                | KeyCode::Enter
+                | KeyCode::Char(' ')
+                | KeyCode::Char('l')
+                | KeyCode::Char('j') => current = (current + 1).min(last),
+                KeyCode::Left
+                | KeyCode::Up
+                | KeyCode::PageUp
+                | KeyCode::Backspace
+                | KeyCode::Char('h')
+                | KeyCode::Char('k') => current = current.saturating_sub(1),
+                KeyCode::Home | KeyCode::Char('g') => current = 0,
+                KeyCode::End | KeyCode::Char('G') => current = last,
+                KeyCode::Char('c') if modifiers.contains(KeyModifiers::CONTROL) => break,
+                KeyCode::Char('q') | KeyCode::Esc => break,
+                _ => {}
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn draw(
+    stdout: &mut Stdout,
+    renderer: &Renderer,
+    document: &Document,
+    current: usize,
+) -> Result<()> {
+    let (columns, rows) = terminal::size()?;
+    let width = columns.saturating_sub(2 * MARGIN) as usize;
+    let lines = renderer.render(&document.slides[current], width);
+
+    queue!(stdout, Clear(ClearType::All))?;
+
+    // The last row is the status bar
+    for (row, line) in lines
+        .iter()
+        .take(rows.saturating_sub(3) as usize)
+        .enumerate()
+    {
+        queue!(stdout, cursor::MoveTo(MARGIN, row as u16 + 1))?;
+        for text in line {
+            print_styled(stdout, text)?;
+        }
+    }
+
+    let position = format!("{} / {}", current + 1, document.slides.len());
+    queue!(
+        stdout,
+        cursor::MoveTo(MARGIN, rows.saturating_sub(1)),
+        SetAttribute(Attribute::Dim),
+        Print(&document.title),
+        cursor::MoveTo(
+            columns.saturating_sub(MARGIN + position.chars().count() as u16),
+            rows.saturating_sub(1)
+        ),
+        Print(&position),
+        SetAttribute(Attribute::Reset)
+    )?;
+
+    stdout.flush()?;
+    Ok(())
+}
+
+fn print_styled(stdout: &mut Stdout, text: &StyledText) -> Result<()> {
+    let style = text.style;
+
+    if style.bold {
+        queue!(stdout, SetAttribute(Attribute::Bold))?;
+    }
+    if style.italic {
+        queue!(stdout, SetAttribute(Attribute::Italic))?;
+    }
+    if style.underline {
+        queue!(stdout, SetAttribute(Attribute::Underlined))?;
+    }
+    if style.dim {
+        queue!(stdout, SetAttribute(Attribute::Dim))?;
+    }
+    if let Some((r, g, b)) = style.color {
+        queue!(stdout, SetForegroundColor(Color::Rgb { r, g, b }))?;
+    }
+
+    queue!(stdout, Print(&text.text), SetAttribute(Attribute::Reset))?;
+    Ok(())
+}
+
+/// Raw mode and alternate screen, restored when dropped even on error
+struct RawTerminal;
+
+impl RawTerminal {
+    fn enter() -> Result<Self> {
+        terminal::enable_raw_mode()?;
+        execute!(io::stdout(), EnterAlternateScreen, cursor::Hide)?;
+        Ok(RawTerminal)
+    }
+}
+
+impl Drop for RawTerminal {
+    fn drop(&mut self) {
+        let _ = execute!(io::stdout(), cursor::Show, LeaveAlternateScreen);
+        let _ = terminal::disable_raw_mode();
+    }
+}
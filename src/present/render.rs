@@ -0,0 +1,353 @@
+use crate::document::{plain_text, Block, DocumentSlide, ListItem, Row, Span};
+use syntect::{
+    easy::HighlightLines,
+    highlighting::{Theme, ThemeSet},
+    parsing::SyntaxSet,
+    util::LinesWithEndings,
+};
+
+const ACCENT: (u8, u8, u8) = (29, 161, 242);
+const INLINE_CODE: (u8, u8, u8) = (230, 126, 34);
+const CODE_THEME: &str = "base16-ocean.dark";
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct TextStyle {
+    pub bold: bool,
+    pub italic: bool,
+    pub underline: bool,
+    pub dim: bool,
+    pub color: Option<(u8, u8, u8)>,
+}
+
+/// A run of text sharing the same style
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StyledText {
+    pub text: String,
+    pub style: TextStyle,
+}
+
+pub type Line = Vec<StyledText>;
+
+/// Lay out slides as styled terminal lines
+pub struct Renderer {
+    syntaxes: SyntaxSet,
+    theme: Theme,
+}
+
+impl Default for Renderer {
+    fn default() -> Self {
+        let mut themes = ThemeSet::load_defaults();
+        Renderer {
+            syntaxes: SyntaxSet::load_defaults_newlines(),
+            theme: themes.themes.remove(CODE_THEME).unwrap_or_default(),
+        }
+    }
+}
+
+impl Renderer {
+    /// Render the slide content, wrapping text to `width` columns
+    pub fn render(&self, slide: &DocumentSlide, width: usize) -> Vec<Line> {
+        let width = width.max(10);
+        let mut lines = vec![];
+
+        for block in slide.blocks.iter() {
+            match block {
+                Block::Heading { level, content } => {
+                    let style = TextStyle {
+                        bold: true,
+                        underline: *level == 1,
+                        color: if *level <= 2 { Some(ACCENT) } else { None },
+                        ..TextStyle::default()
+                    };
+                    lines.extend(wrap(content, style, width, "", ""));
+                }
+                Block::Paragraph(content) => {
+                    lines.extend(wrap(content, TextStyle::default(), width, "", ""))
+                }
+                Block::Quote(content) => {
+                    let style = TextStyle {
+                        italic: true,
+                        ..TextStyle::default()
+                    };
+                    lines.extend(wrap(content, style, width, "│ ", "│ "));
+                }
+                Block::List { ordered, items } => lines.extend(list(*ordered, items, width)),
+                Block::Code { language, code } => {
+                    lines.extend(self.code(language.as_deref(), code))
+                }
+                Block::Image { alt, .. } => lines.push(vec![styled(
+                    &format!("[image: {}]", alt),
+                    TextStyle {
+                        dim: true,
+                        italic: true,
+                        ..TextStyle::default()
+                    },
+                )]),
+                Block::Table { header, rows } => lines.extend(table(header, rows)),
+                Block::Rule => lines.push(vec![styled(
+                    &"─".repeat(width),
+                    TextStyle {
+                        dim: true,
+                        ..TextStyle::default()
+                    },
+                )]),
+            }
+
+            // Blocks are separated by an empty line
+            lines.push(vec![]);
+        }
+
+        lines.pop();
+        lines
+    }
+
+    fn code(&self, language: Option<&str>, code: &str) -> Vec<Line> {
+        let syntax = language
+            .and_then(|language| self.syntaxes.find_syntax_by_token(language))
+            .unwrap_or_else(|| self.syntaxes.find_syntax_plain_text());
+        let mut highlighter = HighlightLines::new(syntax, &self.theme);
+
+        LinesWithEndings::from(code)
+            .map(|line| {
+                let mut result = vec![styled("  ", TextStyle::default())];
+                match highlighter.highlight_line(line, &self.syntaxes) {
+                    Ok(regions) => {
+                        for (style, text) in regions {
+                            let color = style.foreground;
+                            let style = TextStyle {
+                                color: Some((color.r, color.g, color.b)),
+                                ..TextStyle::default()
+                            };
+                            push(&mut result, text.trim_end_matches(['\n', '\r']), style);
+                        }
+                    }
+                    Err(_) => push(&mut result, line.trim_end(), TextStyle::default()),
+                }
+                result
+            })
+            .collect()
+    }
+}
+
+fn list(ordered: bool, items: &[ListItem], width: usize) -> Vec<Line> {
+    let mut lines = vec![];
+    // Item number of each nesting level
+    let mut counters: Vec<usize> = vec![];
+
+    for item in items {
+        counters.truncate(item.level + 1);
+        counters.resize(item.level + 1, 0);
+        counters[item.level] += 1;
+
+        let indent = "  ".repeat(item.level);
+        let marker = if ordered {
+            format!("{}{}. ", indent, counters[item.level])
+        } else {
+            format!("{}• ", indent)
+        };
+        let continuation = " ".repeat(marker.chars().count());
+
+        lines.extend(wrap(
+            &item.content,
+            TextStyle::default(),
+            width,
+            &marker,
+            &continuation,
+        ));
+    }
+
+    lines
+}
+
+fn table(header: &Row, rows: &[Row]) -> Vec<Line> {
+    let columns = header
+        .len()
+        .max(rows.iter().map(Vec::len).max().unwrap_or(0));
+    let cell_text = |row: &Row, column: usize| {
+        row.get(column)
+            .map(|cell| plain_text(cell))
+            .unwrap_or_default()
+    };
+
+    let widths: Vec<usize> = (0..columns)
+        .map(|column| {
+            std::iter::once(header)
+                .chain(rows.iter())
+                .map(|row| cell_text(row, column).chars().count())
+                .max()
+                .unwrap_or(0)
+        })
+        .collect();
+
+    let border = TextStyle {
+        dim: true,
+        ..TextStyle::default()
+    };
+
+    let render_row = |row: &Row, style: TextStyle| {
+        let mut line = vec![];
+        for (column, width) in widths.iter().enumerate() {
+            if column > 0 {
+                push(&mut line, " │ ", border);
+            }
+            let mut text = cell_text(row, column);
+            if column + 1 < widths.len() {
+                text.push_str(&" ".repeat(width - text.chars().count()));
+            }
+            push(&mut line, &text, style);
+        }
+        line
+    };
+
+    let separator = widths
+        .iter()
+        .map(|width| "─".repeat(*width))
+        .collect::<Vec<String>>()
+        .join("─┼─");
+
+    let mut lines = vec![
+        render_row(
+            header,
+            TextStyle {
+                bold: true,
+                ..TextStyle::default()
+            },
+        ),
+        vec![styled(&separator, border)],
+    ];
+    lines.extend(rows.iter().map(|row| render_row(row, TextStyle::default())));
+    lines
+}
+
+/// Word wrap styled spans, `prefix` starting the first line and
+/// `continuation` the following ones
+fn wrap(
+    spans: &[Span],
+    base: TextStyle,
+    width: usize,
+    prefix: &str,
+    continuation: &str,
+) -> Vec<Line> {
+    let prefix_style = TextStyle {
+        dim: base.italic,
+        ..TextStyle::default()
+    };
+
+    let mut lines = vec![];
+    let mut line = vec![styled(prefix, prefix_style)];
+    let mut length = prefix.chars().count();
+    let mut line_start = length;
+
+    for span in spans {
+        let style = TextStyle {
+            bold: base.bold || span.bold,
+            italic: base.italic || span.italic,
+            underline: base.underline || span.link.is_some(),
+            dim: base.dim,
+            color: if span.code {
+                Some(INLINE_CODE)
+            } else if span.link.is_some() {
+                Some(ACCENT)
+            } else {
+                base.color
+            },
+        };
+
+        for word in span.text.split_inclusive(' ') {
+            let word_length = word.trim_end().chars().count();
+
+            if length + word_length > width && length > line_start {
+                lines.push(std::mem::take(&mut line));
+                line = vec![styled(continuation, prefix_style)];
+                length = continuation.chars().count();
+                line_start = length;
+            }
+
+            push(&mut line, word, style);
+            length += word.chars().count();
+        }
+    }
+
+    lines.push(line);
+    lines
+        .into_iter()
+        .map(|mut line| {
+            // Drop the empty prefixes and the trailing spaces of wrapped lines
+            line.retain(|text| !text.text.is_empty());
+            if let Some(last) = line.last_mut() {
+                last.text = last.text.trim_end().to_string();
+            }
+            line
+        })
+        .collect()
+}
+
+fn styled(text: &str, style: TextStyle) -> StyledText {
+    StyledText {
+        text: text.to_string(),
+        style,
+    }
+}
+
+/// Append text to the line, merging it with the previous run when the style does not change
+fn push(line: &mut Line, text: &str, style: TextStyle) {
+    match line.last_mut() {
+        Some(last) if last.style == style => last.text.push_str(text),
+        _ => line.push(styled(text, style)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        document::Document,
+        present::render::{Line, Renderer},
+    };
+
+    fn text(lines: &[Line]) -> Vec<String> {
+        lines
+            .iter()
+            .map(|line| line.iter().map(|text| text.text.as_str()).collect())
+            .collect()
+    }
+
+    #[test]
+    fn should_render_slide_as_terminal_lines() {
+        let document = Document::parse(
+            "demo",
+            "EN",
+            &[(
+                "slides/a.md".into(),
+                "# Hello\n\nSome **bold** words to wrap\n\n1. one\n    1. nested\n2. two\n\n| a | bb |\n|---|---|\n| 1 | 2 |\n\n```rust\nlet a = 1;\n```\n".into(),
+            )],
+        );
+
+        let lines = Renderer::default().render(&document.slides[0], 16);
+
+        assert_eq!(
+            text(&lines),
+            vec![
+                "Hello",
+                "",
+                "Some bold words",
+                "to wrap",
+                "",
+                "1. one",
+                "  1. nested",
+                "2. two",
+                "",
+                "a │ bb",
+                "──┼───",
+                "1 │ 2",
+                "",
+                "  let a = 1;",
+            ]
+        );
+
+        // Bold text keeps its style and code is highlighted
+        assert!(lines[2]
+            .iter()
+            .any(|text| text.text == "bold" && text.style.bold));
+        assert!(lines[13].len() > 2);
+    }
+}
@@ -0,0 +1,291 @@
+use flate2::{write::GzEncoder, Compression};
+use percent_encoding::percent_decode_str;
+use std::{
+    collections::{hash_map::DefaultHasher, HashMap},
+    hash::{Hash, Hasher},
+    io::Write,
+    path::{Component, Path, PathBuf},
+    sync::{Arc, Mutex},
+};
+use warp::{
+    http::{header, Response, StatusCode},
+    hyper::Body,
+    path::Tail,
+    reject, Filter, Rejection, Reply,
+};
+
+/// How long browsers may reuse a served file before asking for it again
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CachePolicy {
+    /// Files change with every build, browsers revalidate them with their ETag
+    Development,
+    /// Release build, pages are revalidated and assets are reused for an hour
+    Production,
+}
+
+impl CachePolicy {
+    fn cache_control(&self, path: &Path) -> &'static str {
+        let extension = path.extension().and_then(|extension| extension.to_str());
+        let is_font = matches!(extension, Some("eot" | "ttf" | "woff" | "woff2"));
+        let is_page = matches!(extension, Some("html"));
+
+        match self {
+            // Bundled fonts never change between builds
+            CachePolicy::Development if is_font => "public, max-age=86400",
+            CachePolicy::Development => "no-cache",
+            CachePolicy::Production if is_page => "no-cache",
+            CachePolicy::Production => "public, max-age=3600",
+        }
+    }
+}
+
+/// Content encoding of a response
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum Encoding {
+    Identity,
+    Gzip,
+    Brotli,
+}
+
+impl Encoding {
+    /// Preferred encoding accepted by the browser, brotli first
+    fn negotiate(accept_encoding: Option<&str>) -> Encoding {
+        let accepted: Vec<&str> = accept_encoding
+            .unwrap_or_default()
+            .split(',')
+            .filter_map(|encoding| {
+                let mut parts = encoding.split(';').map(str::trim);
+                let name = parts.next()?;
+                // `gzip;q=0` explicitly refuses an encoding
+                let refused = parts.any(|param| {
+                    param
+                        .strip_prefix("q=")
+                        .and_then(|quality| quality.parse::<f32>().ok())
+                        == Some(0.0)
+                });
+                (!refused).then_some(name)
+            })
+            .collect();
+
+        if accepted.contains(&"br") {
+            Encoding::Brotli
+        } else if accepted.contains(&"gzip") {
+            Encoding::Gzip
+        } else {
+            Encoding::Identity
+        }
+    }
+
+    fn header(&self) -> Option<&'static str> {
+        match self {
+            Encoding::Identity => None,
+            Encoding::Gzip => Some("gzip"),
+            Encoding::Brotli => Some("br"),
+        }
+    }
+
+    fn compress(&self, content: &[u8]) -> std::io::Result<Vec<u8>> {
+        match self {
+            Encoding::Identity => Ok(content.to_vec()),
+            Encoding::Gzip => {
+                let mut encoder = GzEncoder::new(vec![], Compression::default());
+                encoder.write_all(content)?;
+                encoder.finish()
+            }
+            Encoding::Brotli => {
+                let mut encoder = brotli::CompressorWriter::new(vec![], 4096, 5, 22);
+                encoder.write_all(content)?;
+                Ok(encoder.into_inner())
+            }
+        }
+    }
+}
+
+/// Compressed files, reused until their content changes
+type CompressionCache = Arc<Mutex<HashMap<(PathBuf, Encoding), (String, Vec<u8>)>>>;
+
+/// Serve the files of `public_dir` with ETags, cache headers and gzip or brotli compression
+pub fn route(
+    public_dir: PathBuf,
+    policy: CachePolicy,
+) -> impl Filter<Extract = (impl Reply,), Error = Rejection> + Clone {
+    let cache = CompressionCache::default();
+
+    warp::get()
+        .or(warp::head())
+        .unify()
+        .and(warp::path::tail())
+        .and(warp::header::optional::<String>("accept-encoding"))
+        .and(warp::header::optional::<String>("if-none-match"))
+        .and_then(
+            move |tail: Tail, accept_encoding: Option<String>, if_none_match: Option<String>| {
+                let public_dir = public_dir.clone();
+                let cache = cache.clone();
+                async move {
+                    let path = resolve(&public_dir, tail.as_str()).ok_or_else(reject::not_found)?;
+                    let content = tokio::fs::read(&path)
+                        .await
+                        .map_err(|_| reject::not_found())?;
+
+                    let encoding = if is_compressible(&path) {
+                        Encoding::negotiate(accept_encoding.as_deref())
+                    } else {
+                        Encoding::Identity
+                    };
+
+                    tokio::task::spawn_blocking(move || {
+                        respond(
+                            &path,
+                            &content,
+                            encoding,
+                            policy,
+                            if_none_match.as_deref(),
+                            &cache,
+                        )
+                    })
+                    .await
+                    .map_err(|_| reject::not_found())
+                }
+            },
+        )
+}
+
+fn respond(
+    path: &Path,
+    content: &[u8],
+    encoding: Encoding,
+    policy: CachePolicy,
+    if_none_match: Option<&str>,
+    cache: &CompressionCache,
+) -> Response<Body> {
+    let etag = etag(content, encoding);
+    let mime = mime_guess::from_path(path).first_or_octet_stream();
+
+    let mut response = Response::builder()
+        .header(header::ETAG, &etag)
+        .header(header::CACHE_CONTROL, policy.cache_control(path));
+    if is_compressible(path) {
+        response = response.header(header::VARY, "Accept-Encoding");
+    }
+
+    if if_none_match.is_some_and(|tags| etag_matches(tags, &etag)) {
+        return response
+            .status(StatusCode::NOT_MODIFIED)
+            .body(Body::empty())
+            .unwrap();
+    }
+
+    let key = (path.to_path_buf(), encoding);
+    let cached = cache
+        .lock()
+        .unwrap()
+        .get(&key)
+        .filter(|(cached_etag, _)| *cached_etag == etag)
+        .map(|(_, body)| body.clone());
+
+    let (encoding, body) = match cached {
+        Some(body) => (encoding, body),
+        None => match encoding.compress(content) {
+            Ok(body) => {
+                if encoding != Encoding::Identity {
+                    cache.lock().unwrap().insert(key, (etag, body.clone()));
+                }
+                (encoding, body)
+            }
+            Err(_) => (Encoding::Identity, content.to_vec()),
+        },
+    };
+
+    if let Some(content_encoding) = encoding.header() {
+        response = response.header(header::CONTENT_ENCODING, content_encoding);
+    }
+
+    response
+        .header(header::CONTENT_TYPE, mime.as_ref())
+        .header(header::CONTENT_LENGTH, body.len())
+        .body(Body::from(body))
+        .unwrap()
+}
+
+/// File of the public directory requested by a url path, `index.html` for directories
+fn resolve(public_dir: &Path, url_path: &str) -> Option<PathBuf> {
+    let decoded = percent_decode_str(url_path).decode_utf8().ok()?;
+    let relative = Path::new(decoded.as_ref());
+
+    // Never serve files outside of the public directory
+    if relative
+        .components()
+        .any(|component| !matches!(component, Component::Normal(_)))
+    {
+        return None;
+    }
+
+    let path = public_dir.join(relative);
+    if path.is_dir() {
+        Some(path.join("index.html"))
+    } else {
+        Some(path)
+    }
+}
+
+/// Text formats worth compressing, images and woff fonts already are
+fn is_compressible(path: &Path) -> bool {
+    matches!(
+        path.extension().and_then(|extension| extension.to_str()),
+        Some("html" | "css" | "js" | "json" | "svg" | "txt" | "xml" | "map" | "eot" | "ttf")
+    )
+}
+
+/// Strong ETag of a file content, each encoding has its own
+fn etag(content: &[u8], encoding: Encoding) -> String {
+    let mut hasher = DefaultHasher::new();
+    content.hash(&mut hasher);
+    match encoding.header() {
+        Some(encoding) => format!("\"{:016x}-{}\"", hasher.finish(), encoding),
+        None => format!("\"{:016x}\"", hasher.finish()),
+    }
+}
+
+fn etag_matches(if_none_match: &str, etag: &str) -> bool {
+    if_none_match
+        .split(',')
+        .map(|tag| tag.trim().trim_start_matches("W/"))
+        .any(|tag| tag == "*" || tag == etag)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::server::files::{etag_matches, resolve, CachePolicy, Encoding};
+    use std::path::{Path, PathBuf};
+
+    #[test]
+    fn should_negotiate_encoding() {
+        assert_eq!(
+            Encoding::negotiate(Some("gzip, deflate, br")),
+            Encoding::Brotli
+        );
+        assert_eq!(
+            Encoding::negotiate(Some("br;q=0, gzip;q=0.8")),
+            Encoding::Gzip
+        );
+        assert_eq!(Encoding::negotiate(Some("identity")), Encoding::Identity);
+        assert_eq!(Encoding::negotiate(None), Encoding::Identity);
+    }
+
+    #[test]
+    fn should_resolve_files_inside_public_dir() {
+        let public = Path::new("public");
+
+        assert_eq!(
+            resolve(public, "fontawesome/css/font%20awesome.css"),
+            Some(PathBuf::from("public/fontawesome/css/font awesome.css"))
+        );
+        assert_eq!(resolve(public, "../unveil.toml"), None);
+        assert_eq!(resolve(public, "%2e%2e/unveil.toml"), None);
+        assert!(etag_matches(r#"W/"abc", "def""#, r#""def""#));
+        assert_eq!(
+            CachePolicy::Production.cache_control(Path::new("public/index.html")),
+            "no-cache"
+        );
+    }
+}
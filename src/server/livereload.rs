@@ -0,0 +1,458 @@
+use crate::unveil::BuildError;
+use futures_util::{SinkExt, StreamExt};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{hash_map::DefaultHasher, BTreeMap, HashMap},
+    fs,
+    hash::{Hash, Hasher},
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+};
+use tokio::sync::broadcast::{self, error::RecvError};
+use warp::{
+    ws::{Message, WebSocket, Ws},
+    Filter, Rejection, Reply,
+};
+
+/// Path of the reload socket, next to `livereload.js`
+pub const LIVERELOAD_PATH: &str = "__livereload";
+
+/// Message exchanged with the pages, after a rebuild or when a page changes slide
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum LiveMessage {
+    Reload {
+        /// Name of the edited slide, pages jump to it after reloading
+        #[serde(skip_serializing_if = "Option::is_none")]
+        slide: Option<String>,
+    },
+    /// Only stylesheets changed, pages swap them in place and keep their position
+    Css {
+        /// Urls of the stylesheets from the root of the public directory
+        stylesheets: Vec<String>,
+    },
+    /// The build failed, pages show an overlay until the next successful one
+    Error {
+        #[serde(skip_serializing_if = "Option::is_none")]
+        file: Option<String>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        line: Option<usize>,
+        message: String,
+    },
+    /// A build succeeded after an error without changing the output, pages hide the overlay
+    Recovered,
+    /// Sent by a presenter page moving to another slide, relayed to the other windows of the deck
+    Navigate {
+        /// Url path of the deck directory, `/` for single deck projects
+        deck: String,
+        /// Index of the slide, from 0
+        slide: usize,
+        /// Number of fragments revealed on the slide
+        #[serde(default)]
+        fragment: usize,
+    },
+}
+
+impl LiveMessage {
+    /// Pick the message for the files that changed between two builds,
+    /// pages are left alone when the build output is the same
+    pub fn for_changes(
+        before: &Snapshot,
+        after: &Snapshot,
+        slide: Option<String>,
+    ) -> Option<LiveMessage> {
+        let changed: Vec<&PathBuf> = after
+            .files
+            .iter()
+            .filter(|(path, hash)| before.files.get(*path) != Some(*hash))
+            .map(|(path, _)| path)
+            .chain(
+                before
+                    .files
+                    .keys()
+                    .filter(|path| !after.files.contains_key(*path)),
+            )
+            .collect();
+
+        if changed.is_empty() {
+            return None;
+        }
+
+        let style_only = changed
+            .iter()
+            .all(|path| path.extension().is_some_and(|extension| extension == "css"));
+
+        Some(if style_only {
+            let mut stylesheets: Vec<String> = changed
+                .iter()
+                .map(|path| {
+                    let url: Vec<String> = path
+                        .components()
+                        .map(|component| component.as_os_str().to_string_lossy().to_string())
+                        .collect();
+                    format!("/{}", url.join("/"))
+                })
+                .collect();
+            stylesheets.sort();
+            LiveMessage::Css { stylesheets }
+        } else {
+            LiveMessage::Reload { slide }
+        })
+    }
+
+    pub fn for_error(err: &anyhow::Error) -> LiveMessage {
+        match err.downcast_ref::<BuildError>() {
+            Some(err) => LiveMessage::Error {
+                file: Some(err.file.clone()),
+                line: err.line,
+                message: err.message.clone(),
+            },
+            None => LiveMessage::Error {
+                file: None,
+                line: None,
+                message: err.to_string(),
+            },
+        }
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).expect("Live message should serialize")
+    }
+}
+
+/// Content hash of every generated file, relative to the public directory
+#[derive(Default)]
+pub struct Snapshot {
+    files: HashMap<PathBuf, u64>,
+}
+
+impl Snapshot {
+    pub fn new(public_dir: &Path) -> Snapshot {
+        let mut snapshot = Snapshot::default();
+        snapshot.add_dir(public_dir, public_dir);
+        snapshot
+    }
+
+    fn add_dir(&mut self, root: &Path, dir: &Path) {
+        let Ok(entries) = fs::read_dir(dir) else {
+            return;
+        };
+
+        for path in entries.flatten().map(|entry| entry.path()) {
+            if path.is_dir() {
+                self.add_dir(root, &path);
+            } else if let (Ok(relative), Ok(content)) = (path.strip_prefix(root), fs::read(&path)) {
+                let mut hasher = DefaultHasher::new();
+                content.hash(&mut hasher);
+                self.files.insert(relative.to_path_buf(), hasher.finish());
+            }
+        }
+    }
+}
+
+/// Sends the live messages to every connected page
+#[derive(Clone)]
+pub struct LiveReload {
+    messages: broadcast::Sender<String>,
+    state: Arc<Mutex<PageState>>,
+    /// Pages opened with `?key=<presenter_key>` lead the navigation, the others follow
+    presenter_key: String,
+}
+
+/// What the pages opened after a message should still be told
+#[derive(Default)]
+struct PageState {
+    /// Error of the last build
+    error: Option<String>,
+    /// Current slide and fragment of each deck
+    positions: BTreeMap<String, (usize, usize)>,
+}
+
+impl PageState {
+    fn replay(&self) -> Vec<String> {
+        self.error
+            .iter()
+            .cloned()
+            .chain(self.positions.iter().map(|(deck, (slide, fragment))| {
+                LiveMessage::Navigate {
+                    deck: deck.clone(),
+                    slide: *slide,
+                    fragment: *fragment,
+                }
+                .to_json()
+            }))
+            .collect()
+    }
+}
+
+impl LiveReload {
+    pub fn new() -> LiveReload {
+        let (messages, _) = broadcast::channel(16);
+        LiveReload {
+            messages,
+            state: Arc::new(Mutex::new(PageState::default())),
+            presenter_key: random_key(),
+        }
+    }
+
+    pub fn presenter_key(&self) -> &str {
+        &self.presenter_key
+    }
+
+    fn is_presenter(&self, query: &HashMap<String, String>) -> bool {
+        query
+            .get("key")
+            .is_some_and(|key| constant_time_eq(key.as_bytes(), self.presenter_key.as_bytes()))
+    }
+
+    pub fn send(&self, message: LiveMessage) {
+        send(&self.messages, &self.state, message);
+    }
+
+    /// Whether the pages currently show a build error
+    pub fn has_error(&self) -> bool {
+        self.state.lock().unwrap().error.is_some()
+    }
+
+    /// Websocket route forwarding the messages to the pages
+    pub fn route(&self) -> impl Filter<Extract = (impl Reply,), Error = Rejection> + Clone {
+        let reload = self.clone();
+        // Presenter pages of multi-deck projects connect next to their deck
+        let path = warp::path(LIVERELOAD_PATH)
+            .or(warp::path::param::<String>()
+                .and(warp::path(LIVERELOAD_PATH))
+                .map(|_deck| ())
+                .untuple_one())
+            .unify();
+
+        path.and(warp::path::end())
+            .and(warp::ws())
+            .and(warp::query::<HashMap<String, String>>())
+            .map(move |ws: Ws, query: HashMap<String, String>| {
+                let messages = reload.messages.subscribe();
+                // Only presenters move the other pages, pages must not keep the channel
+                // open once the server stops sending
+                let relay = reload
+                    .is_presenter(&query)
+                    .then(|| reload.messages.downgrade());
+                let state = reload.state.clone();
+                ws.on_upgrade(move |socket| forward(socket, messages, relay, state))
+            })
+    }
+}
+
+fn send(messages: &broadcast::Sender<String>, state: &Mutex<PageState>, message: LiveMessage) {
+    let json = message.to_json();
+    let mut state = state.lock().unwrap();
+    match message {
+        LiveMessage::Error { .. } => state.error = Some(json.clone()),
+        LiveMessage::Navigate {
+            deck,
+            slide,
+            fragment,
+        } => {
+            state.positions.insert(deck, (slide, fragment));
+        }
+        _ => state.error = None,
+    }
+    let _ = messages.send(json);
+}
+
+async fn forward(
+    socket: WebSocket,
+    mut messages: broadcast::Receiver<String>,
+    relay: Option<broadcast::WeakSender<String>>,
+    state: Arc<Mutex<PageState>>,
+) {
+    let (mut sender, mut receiver) = socket.split();
+
+    let replay = state.lock().unwrap().replay();
+    for message in replay {
+        if sender.send(Message::text(message)).await.is_err() {
+            return;
+        }
+    }
+
+    loop {
+        tokio::select! {
+            message = messages.recv() => match message {
+                Ok(message) => {
+                    if sender.send(Message::text(message)).await.is_err() {
+                        break;
+                    }
+                }
+                // Only the latest messages matter to a page that could not keep up
+                Err(RecvError::Lagged(_)) => continue,
+                Err(RecvError::Closed) => break,
+            },
+            incoming = receiver.next() => match incoming {
+                Some(Ok(incoming)) => {
+                    let navigate = incoming
+                        .to_str()
+                        .ok()
+                        .and_then(|text| serde_json::from_str::<LiveMessage>(text).ok())
+                        .filter(|message| matches!(message, LiveMessage::Navigate { .. }));
+
+                    if let (Some(navigate), Some(messages)) = (navigate, relay.as_ref().and_then(|relay| relay.upgrade())) {
+                        send(&messages, &state, navigate);
+                    }
+                }
+                _ => break,
+            }
+        }
+    }
+
+    // The server is shutting down or the page left
+    let _ = sender.close().await;
+}
+
+/// Random 128 bits hex key from the OS generator, unique to each server run
+fn random_key() -> String {
+    let mut bytes = [0u8; 16];
+    getrandom::getrandom(&mut bytes).expect("Unable to generate the presenter key");
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+/// Compare keys without leaking the length of their common prefix through timing
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |diff, (x, y)| diff | (x ^ y)) == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        server::livereload::{LiveMessage, LiveReload, Snapshot},
+        unveil::BuildError,
+    };
+    use std::{collections::HashMap, path::PathBuf};
+
+    fn snapshot(files: &[(&str, u64)]) -> Snapshot {
+        Snapshot {
+            files: files
+                .iter()
+                .map(|(path, hash)| (PathBuf::from(path), *hash))
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn should_swap_stylesheets_when_only_css_changed() {
+        let before = snapshot(&[
+            ("index.html", 1),
+            ("user_css.css", 1),
+            ("deck/theme.css", 1),
+        ]);
+        let after = snapshot(&[
+            ("index.html", 1),
+            ("user_css.css", 2),
+            ("deck/theme.css", 2),
+        ]);
+
+        let message = LiveMessage::for_changes(&before, &after, None).unwrap();
+
+        assert_eq!(
+            message,
+            LiveMessage::Css {
+                stylesheets: vec!["/deck/theme.css".into(), "/user_css.css".into()]
+            }
+        );
+        assert_eq!(
+            message.to_json(),
+            r#"{"type":"css","stylesheets":["/deck/theme.css","/user_css.css"]}"#
+        );
+    }
+
+    #[test]
+    fn should_reload_when_html_changed() {
+        let before = snapshot(&[("index.html", 1), ("user_css.css", 1)]);
+        let after = snapshot(&[("index.html", 2), ("user_css.css", 2)]);
+
+        let message = LiveMessage::for_changes(&before, &after, Some("02_intro".into()));
+
+        assert_eq!(
+            message,
+            Some(LiveMessage::Reload {
+                slide: Some("02_intro".into())
+            })
+        );
+        assert_eq!(
+            message.unwrap().to_json(),
+            r#"{"type":"reload","slide":"02_intro"}"#
+        );
+        assert_eq!(LiveMessage::for_changes(&after, &after, None), None);
+    }
+
+    #[test]
+    fn should_replay_error_and_positions_to_new_pages() {
+        let reload = LiveReload::new();
+        reload.send(LiveMessage::Navigate {
+            deck: "/".into(),
+            slide: 3,
+            fragment: 1,
+        });
+        reload.send(LiveMessage::for_error(&anyhow::anyhow!("Broken")));
+
+        assert_eq!(
+            reload.state.lock().unwrap().replay(),
+            vec![
+                r#"{"type":"error","message":"Broken"}"#.to_string(),
+                r#"{"type":"navigate","deck":"/","slide":3,"fragment":1}"#.to_string()
+            ]
+        );
+
+        // Navigating keeps the error on screen, a successful build clears it
+        reload.send(LiveMessage::Navigate {
+            deck: "/".into(),
+            slide: 4,
+            fragment: 0,
+        });
+        assert!(reload.has_error());
+        reload.send(LiveMessage::Recovered);
+        assert_eq!(
+            reload.state.lock().unwrap().replay(),
+            vec![r#"{"type":"navigate","deck":"/","slide":4,"fragment":0}"#.to_string()]
+        );
+    }
+
+    #[test]
+    fn should_only_let_presenters_lead() {
+        let reload = LiveReload::new();
+        let query = |key: &str| HashMap::from([("key".to_string(), key.to_string())]);
+
+        assert_eq!(reload.presenter_key().len(), 32);
+        assert_ne!(reload.presenter_key(), LiveReload::new().presenter_key());
+        assert!(reload.is_presenter(&query(reload.presenter_key())));
+        assert!(!reload.is_presenter(&query("guess")));
+        assert!(!reload.is_presenter(&query(&reload.presenter_key()[..31])));
+        assert!(!reload.is_presenter(&HashMap::new()));
+
+        // Positions without a fragment start the slide with none revealed
+        assert_eq!(
+            serde_json::from_str::<LiveMessage>(r#"{"type":"navigate","deck":"/","slide":2}"#)
+                .unwrap(),
+            LiveMessage::Navigate {
+                deck: "/".into(),
+                slide: 2,
+                fragment: 0
+            }
+        );
+    }
+
+    #[test]
+    fn should_locate_build_errors() {
+        let err = anyhow::Error::new(BuildError::new(
+            "slides/02_intro.md",
+            Some(4),
+            "Undefined variable: \"$accent\".",
+        ));
+
+        assert_eq!(
+            LiveMessage::for_error(&err).to_json(),
+            r#"{"type":"error","file":"slides/02_intro.md","line":4,"message":"Undefined variable: \"$accent\"."}"#
+        );
+        assert_eq!(
+            LiveMessage::for_error(&anyhow::anyhow!("Permission denied")).to_json(),
+            r#"{"type":"error","message":"Permission denied"}"#
+        );
+    }
+}
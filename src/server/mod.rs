@@ -1,108 +1,344 @@
-use crate::unveil::UnveilProject;
-use anyhow::Result;
+use crate::unveil::{slide_name, ProjectInputs, UnveilProject};
+use anyhow::{anyhow, Error, Result};
+use tokio::task::JoinHandle;
+use warp::{filters::BoxedFilter, Filter, Rejection, Reply};
 
 use std::{
     ffi::OsStr,
-    fs,
+    future::Future,
     net::{SocketAddr, ToSocketAddrs},
     path::PathBuf,
+    pin::Pin,
+    str::FromStr,
+    sync::mpsc,
 };
+use tokio::sync::watch;
 
+mod files;
+mod livereload;
+mod presenter;
+
+use files::CachePolicy;
+use livereload::{LiveMessage, LiveReload, Snapshot};
+use watcher::WatchEvent;
+mod tls;
 mod watcher;
 
+pub use tls::Tls;
+
+/// Port the server listens on
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ServerPort {
+    Fixed(u16),
+    /// First free port starting from the default one
+    Auto,
+}
+
+impl FromStr for ServerPort {
+    type Err = Error;
+
+    fn from_str(port: &str) -> Result<Self> {
+        match port {
+            "auto" => Ok(ServerPort::Auto),
+            port => port
+                .parse()
+                .map(ServerPort::Fixed)
+                .map_err(|_| anyhow!("Invalid port {}, expected a number or `auto`", port)),
+        }
+    }
+}
+
 pub struct Server {
-    pub(crate) http_port: i32,
-    pub(crate) ws_port: i32,
+    pub(crate) port: ServerPort,
     pub(crate) hostname: String,
+    /// Serve over https with this certificate
+    pub(crate) tls: Option<Tls>,
+    /// Open the served url in the default browser
+    pub(crate) open: bool,
+    /// Print the url as a json line for the tools driving the server, never open the browser
+    pub(crate) headless: bool,
+    /// Url path the public directory is served from, `/` or the path of `base_url`
+    pub(crate) base_path: String,
     public_dir: PathBuf,
-    slide_dir: PathBuf,
 }
 
 impl Default for Server {
     fn default() -> Self {
         Server {
-            http_port: 7878,
-            ws_port: 3000,
+            port: ServerPort::Fixed(Server::DEFAULT_PORT),
             hostname: "localhost".to_string(),
+            tls: None,
+            open: true,
+            headless: false,
+            base_path: "/".to_string(),
             public_dir: PathBuf::from("public"),
-            slide_dir: PathBuf::from("slides"),
         }
     }
 }
 
 impl Server {
-    pub async fn serve(&self) -> Result<()> {
-        let address = format!("{}:{}", self.hostname, self.http_port);
-        let address = *address
-            .to_socket_addrs()?
-            .collect::<Vec<SocketAddr>>()
-            .first()
-            .unwrap();
-        let ws_adress = format!("{}:{}", self.hostname, self.ws_port);
+    const DEFAULT_PORT: u16 = 7878;
+    // Ports tried by `--port auto`
+    const AUTO_PORTS: u16 = 100;
+
+    /// Serve the public directory, rebuilding the project when one of its `inputs` changes,
+    /// until the process receives SIGINT or SIGTERM
+    pub async fn serve(&self, inputs: ProjectInputs) -> Result<()> {
+        // Pages are notified of rebuilds through a websocket on the http port
+        let reload = LiveReload::new();
+        let (shutdown, _) = watch::channel(());
+
+        let routes = self.serve_routes(&reload);
+        let server = self.listen(routes, &shutdown, Some(reload.presenter_key()))?;
+
         let public_dir = self.public_dir.clone();
+        let mut snapshot = Snapshot::new(&public_dir);
 
-        tokio::spawn(async move {
-            warp::serve(warp::filters::fs::dir(public_dir))
-                .run(address)
-                .await;
-        });
+        let events = mpsc::channel();
+        let stop_watching = events.0.clone();
+        let mut watching = tokio::task::spawn_blocking(move || {
+            let mut last_inputs = inputs.clone();
+            watcher::trigger_on_change(inputs, events, |paths| {
+                let changed: Vec<String> = paths
+                    .iter()
+                    .map(|path| path.display().to_string())
+                    .collect();
+                println!("Rebuilding presentation, changed: {}", changed.join(", "));
 
-        let ws_server = ws::WebSocket::new(|_| |_| Ok(()))?;
+                let mut project = UnveilProject::default();
+                let result = project.build();
 
-        let broadcaster = ws_server.broadcaster();
-        std::thread::spawn(move || {
-            ws_server
-                .listen(ws_adress)
-                .expect("Error Opening websocket");
-        });
+                if let Err(e) = result {
+                    eprintln!("Unable to load the presentation : {}", e);
+                    reload.send(LiveMessage::for_error(&e));
+                    // A failed build stops reading its inputs early, keep watching the previous ones
+                    project.inputs.extend(last_inputs.clone());
+                } else {
+                    let build = Snapshot::new(&public_dir);
+                    // Pages jump to the edited slide
+                    let slide = paths
+                        .iter()
+                        .find(|path| path.extension().is_some_and(|extension| extension == "md"))
+                        .and_then(|path| path.file_name())
+                        .map(|filename| slide_name(&filename.to_string_lossy()));
 
-        let serving_url = format!("http://{}", address);
-        println!("Serving on: {}", serving_url);
+                    let message = LiveMessage::for_changes(&snapshot, &build, slide)
+                        .or_else(|| reload.has_error().then_some(LiveMessage::Recovered));
+                    if let Some(message) = message {
+                        reload.send(message);
+                    }
+                    snapshot = build;
+                    last_inputs = project.inputs.clone();
+                }
 
-        let mut paths = vec![];
-        let entries = fs::read_dir(&self.slide_dir)?;
+                project.inputs
+            })
+        });
 
-        for entry in entries {
-            let entry = entry?;
-            paths.push(entry.path());
+        tokio::select! {
+            result = &mut watching => return result.map_err(Error::from),
+            result = stop_signal() => result?,
         }
 
-        paths.push(PathBuf::from("unveil.toml"));
-        paths.push(PathBuf::from("public/unveil.css"));
+        // Wait for the running build, then close the connections
+        println!("Shutting down...");
+        let _ = stop_watching.send(WatchEvent::Stop);
+        watching.await?;
+        let _ = shutdown.send(());
+        server.await?;
+
+        Ok(())
+    }
+
+    fn serve_routes(
+        &self,
+        reload: &LiveReload,
+    ) -> impl Filter<Extract = (impl Reply,), Error = Rejection> + Clone {
+        reload.route().or(presenter::route()).or(files::route(
+            self.public_dir.clone(),
+            CachePolicy::Development,
+        ))
+    }
 
-        open(serving_url);
+    /// Serve `routes` under the base path, the generated pages link to it
+    fn mount<F, R>(&self, routes: F) -> impl Filter<Extract = (R,), Error = Rejection> + Clone
+    where
+        F: Filter<Extract = (R,), Error = Rejection> + Clone + Send + Sync + 'static,
+        R: Reply + 'static,
+    {
+        let prefix = self
+            .base_path
+            .split('/')
+            .filter(|segment| !segment.is_empty())
+            .fold(warp::any().boxed(), |prefix: BoxedFilter<()>, segment| {
+                prefix.and(warp::path(segment.to_string())).boxed()
+            });
 
-        watcher::trigger_on_change(|paths| {
-            println!("Files changed: {:?}", paths);
-            println!("Building presentation...");
+        prefix.and(routes)
+    }
 
-            let mut project = UnveilProject::default();
-            let result = project.build(self);
+    /// Serve the release build in the public directory with production cache headers,
+    /// until the process receives SIGINT or SIGTERM
+    pub async fn preview(&self) -> Result<()> {
+        let (shutdown, _) = watch::channel(());
+        let server = self.listen(
+            files::route(self.public_dir.clone(), CachePolicy::Production),
+            &shutdown,
+            None,
+        )?;
 
-            if let Err(e) = result {
-                eprintln!("Unable to load the presentation : {}", e);
-            } else {
-                let _ = broadcaster.send("reload");
-            }
-        });
+        stop_signal().await?;
+        println!("Shutting down...");
+        let _ = shutdown.send(());
+        server.await?;
 
         Ok(())
     }
 
-    pub fn with_http_port(mut self, port: Option<i32>) -> Server {
-        if let Some(port) = port {
-            self.http_port = port;
+    /// Bind `routes` and serve them until `shutdown` is sent, then report the url.
+    /// With a `presenter_key`, the reported presenter view leads the followers. The opened browser
+    /// shows the plain deck url, the one shared with the audience
+    fn listen<F, R>(
+        &self,
+        routes: F,
+        shutdown: &watch::Sender<()>,
+        presenter_key: Option<&str>,
+    ) -> Result<JoinHandle<()>>
+    where
+        F: Filter<Extract = (R,), Error = Rejection> + Clone + Send + Sync + 'static,
+        R: Reply + 'static,
+    {
+        let routes = self.mount(routes);
+        let ports = match self.port {
+            ServerPort::Fixed(port) => port..=port,
+            ServerPort::Auto => Server::DEFAULT_PORT..=Server::DEFAULT_PORT + Server::AUTO_PORTS,
+        };
+
+        let identity = match &self.tls {
+            Some(tls) => Some(tls.identity(&self.hostname)?),
+            None => None,
+        };
+
+        // Bind before spawning the server so a busy port is reported instead of panicking in the task
+        let mut bind_error = None;
+        let mut bound = None;
+        for port in ports {
+            let address = self.address(port)?;
+
+            let mut stopped = shutdown.subscribe();
+            let signal = async move {
+                let _ = stopped.changed().await;
+            };
+
+            let server = warp::serve(routes.clone());
+            let server = match &identity {
+                Some(identity) => server
+                    .tls()
+                    .cert(&identity.cert)
+                    .key(&identity.key)
+                    .try_bind_with_graceful_shutdown(address, signal)
+                    .map(|(address, server)| (address, Box::pin(server) as ServerFuture)),
+                None => server
+                    .try_bind_with_graceful_shutdown(address, signal)
+                    .map(|(address, server)| (address, Box::pin(server) as ServerFuture)),
+            };
+
+            match server {
+                Ok(server) => {
+                    bound = Some(server);
+                    break;
+                }
+                Err(err) => bind_error = Some(err),
+            }
         }
+
+        let (address, server) = bound.ok_or_else(|| match self.port {
+            ServerPort::Fixed(port) => anyhow!(
+                "Could not listen on {}:{} ({}), choose another port with `--port <PORT>` or `--port auto`",
+                self.hostname,
+                port,
+                bind_error.map(|err| err.to_string()).unwrap_or_default()
+            ),
+            ServerPort::Auto => anyhow!(
+                "No free port between {} and {} on {}",
+                Server::DEFAULT_PORT,
+                Server::DEFAULT_PORT + Server::AUTO_PORTS,
+                self.hostname
+            ),
+        })?;
+
+        let server = tokio::spawn(server);
+
+        let scheme = if identity.is_some() { "https" } else { "http" };
+        let serving_url = format!(
+            "{}://{}:{}{}",
+            scheme,
+            self.hostname,
+            address.port(),
+            self.base_path.trim_end_matches('/')
+        );
+        let presenter_url =
+            presenter_key.map(|key| format!("{}/presenter?key={}", serving_url, key));
+        if self.headless {
+            let mut listening = serde_json::json!({ "url": serving_url, "port": address.port() });
+            if let Some(presenter_url) = presenter_url {
+                listening["presenter"] = presenter_url.into();
+            }
+            println!("{}", listening);
+        } else {
+            println!("Serving on: {}", serving_url);
+            if let Some(presenter_url) = &presenter_url {
+                println!("Presenter view: {}", presenter_url);
+            }
+            if self.open {
+                open(serving_url);
+            }
+        }
+
+        Ok(server)
+    }
+
+    pub fn with_open(mut self, open: bool) -> Server {
+        self.open = open;
+        self
+    }
+
+    pub fn with_headless(mut self, headless: bool) -> Server {
+        self.headless = headless;
         self
     }
 
-    pub fn with_ws_port(mut self, port: Option<i32>) -> Server {
+    /// Serve under the path of the project `base_url`, e.g. `/our-talk/` for
+    /// `https://example.org/our-talk/`
+    pub fn with_base_url(mut self, base_url: &str) -> Server {
+        let path = match base_url.split_once("://") {
+            Some((_, url)) => url.find('/').map(|path| &url[path..]).unwrap_or("/"),
+            None => base_url,
+        };
+        self.base_path = format!("/{}/", path.trim_matches('/')).replace("//", "/");
+        self
+    }
+
+    pub fn with_port(mut self, port: Option<ServerPort>) -> Server {
         if let Some(port) = port {
-            self.ws_port = port;
+            self.port = port;
         }
         self
     }
 
+    fn address(&self, port: u16) -> Result<SocketAddr> {
+        (self.hostname.as_str(), port)
+            .to_socket_addrs()
+            .ok()
+            .and_then(|mut addresses| addresses.next())
+            .ok_or_else(|| anyhow!("Could not resolve hostname {}", self.hostname))
+    }
+
+    pub fn with_tls(mut self, tls: Option<Tls>) -> Server {
+        self.tls = tls;
+        self
+    }
+
     pub fn with_hostname(mut self, hostname: Option<&str>) -> Server {
         if let Some(hostname) = hostname {
             self.hostname = hostname.to_owned();
@@ -111,8 +347,73 @@ impl Server {
     }
 }
 
+/// Resolve on Ctrl-C, or SIGTERM on unix
+async fn stop_signal() -> Result<()> {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{signal, SignalKind};
+        let mut terminate = signal(SignalKind::terminate())?;
+        tokio::select! {
+            result = tokio::signal::ctrl_c() => result?,
+            _ = terminate.recv() => {}
+        }
+    }
+
+    #[cfg(not(unix))]
+    tokio::signal::ctrl_c().await?;
+
+    Ok(())
+}
+
+type ServerFuture = Pin<Box<dyn Future<Output = ()> + Send>>;
+
 fn open<P: AsRef<OsStr>>(path: P) {
     if let Err(e) = open::that(path) {
         eprintln!("Error opening web browser: {}", e);
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::server::{livereload::LiveReload, Server};
+    use std::fs;
+
+    #[tokio::test]
+    async fn should_serve_under_base_url() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("index.html"), "<html></html>").unwrap();
+        fs::write(dir.path().join("unveil.css"), "body {}").unwrap();
+
+        let mut server = Server::default().with_base_url("https://example.org/our-talk");
+        server.public_dir = dir.path().to_path_buf();
+        let reload = LiveReload::new();
+        let routes = server.mount(server.serve_routes(&reload));
+        let status = |path: &'static str| {
+            let routes = routes.clone();
+            async move {
+                warp::test::request()
+                    .path(path)
+                    .reply(&routes)
+                    .await
+                    .status()
+            }
+        };
+
+        assert_eq!(server.base_path, "/our-talk/");
+        assert_eq!(status("/our-talk/").await, 200);
+        assert_eq!(status("/our-talk/unveil.css").await, 200);
+        assert_eq!(status("/our-talk/presenter").await, 200);
+        assert_eq!(status("/unveil.css").await, 404);
+
+        for socket in [
+            "/our-talk/__livereload",
+            "/our-talk/rust-intro/__livereload",
+        ] {
+            assert!(warp::test::ws()
+                .path(socket)
+                .handshake(routes.clone())
+                .await
+                .is_ok());
+        }
+    }
+}
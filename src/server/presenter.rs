@@ -0,0 +1,19 @@
+use crate::assets::PRESENTER_HTML;
+use warp::{path::Tail, reject, reply, Filter, Rejection, Reply};
+
+/// Presenter page of a deck, `/presenter` or `/<deck>/presenter` in multi-deck projects
+pub fn route() -> impl Filter<Extract = (impl Reply,), Error = Rejection> + Clone {
+    warp::get()
+        .and(warp::path::tail())
+        .and_then(|tail: Tail| async move {
+            let segments: Vec<&str> = tail.as_str().split('/').collect();
+            match segments.as_slice() {
+                ["presenter"] | [_, "presenter"] => Ok(reply::with_header(
+                    reply::html(PRESENTER_HTML),
+                    "cache-control",
+                    "no-cache",
+                )),
+                _ => Err(reject::not_found()),
+            }
+        })
+}
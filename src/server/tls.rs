@@ -0,0 +1,120 @@
+use anyhow::{anyhow, Result};
+use std::{
+    fs::{self, OpenOptions},
+    io::Write,
+    path::{Path, PathBuf},
+};
+
+/// Certificate served by `unveil serve --https`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Tls {
+    /// Generated on first use and cached in `.unveil/tls`
+    SelfSigned,
+    /// PEM encoded certificate chain and private key
+    Files { cert: PathBuf, key: PathBuf },
+}
+
+/// PEM encoded certificate chain and private key
+pub(crate) struct Identity {
+    pub cert: Vec<u8>,
+    pub key: Vec<u8>,
+}
+
+impl Tls {
+    pub(crate) fn identity(&self, hostname: &str) -> Result<Identity> {
+        match self {
+            Tls::Files { cert, key } => {
+                let read = |path: &PathBuf| {
+                    fs::read(path)
+                        .map_err(|err| anyhow!("Unable to read {} : {}", path.display(), err))
+                };
+
+                Ok(Identity {
+                    cert: read(cert)?,
+                    key: read(key)?,
+                })
+            }
+            Tls::SelfSigned => self_signed(Path::new(".unveil/tls"), hostname),
+        }
+    }
+}
+
+/// Load the certificate generated for `hostname`, or generate and cache it
+fn self_signed(cache_dir: &Path, hostname: &str) -> Result<Identity> {
+    let cert_path = cache_dir.join(format!("{}.crt", hostname));
+    let key_path = cache_dir.join(format!("{}.key", hostname));
+
+    if let (Ok(cert), Ok(key)) = (fs::read(&cert_path), fs::read(&key_path)) {
+        return Ok(Identity { cert, key });
+    }
+
+    let mut names = vec![
+        "localhost".to_string(),
+        "127.0.0.1".to_string(),
+        "::1".to_string(),
+    ];
+    if !names.iter().any(|name| name == hostname) {
+        names.push(hostname.to_string());
+    }
+
+    let generated = rcgen::generate_simple_self_signed(names)
+        .map_err(|err| anyhow!("Unable to generate a certificate : {}", err))?;
+    let identity = Identity {
+        cert: generated.cert.pem().into_bytes(),
+        key: generated.key_pair.serialize_pem().into_bytes(),
+    };
+
+    fs::create_dir_all(cache_dir)?;
+    fs::write(&cert_path, &identity.cert)?;
+    write_private(&key_path, &identity.key)?;
+
+    Ok(identity)
+}
+
+/// Write the private key readable by its owner only
+fn write_private(path: &Path, content: &[u8]) -> Result<()> {
+    let mut options = OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+
+    options.open(path)?.write_all(content)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::server::tls::self_signed;
+
+    #[test]
+    fn should_cache_self_signed_certificate() {
+        let dir = tempfile::tempdir().unwrap();
+        let cache_dir = dir.path().join("tls");
+
+        let generated = self_signed(&cache_dir, "0.0.0.0").unwrap();
+        let cached = self_signed(&cache_dir, "0.0.0.0").unwrap();
+
+        assert!(String::from_utf8_lossy(&generated.cert).starts_with("-----BEGIN CERTIFICATE-----"));
+        assert!(String::from_utf8_lossy(&generated.key).contains("PRIVATE KEY-----"));
+        assert_eq!(generated.cert, cached.cert);
+        assert_eq!(generated.key, cached.key);
+        assert!(cache_dir.join("0.0.0.0.crt").exists());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn should_only_let_owner_read_the_key() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempfile::tempdir().unwrap();
+        let cache_dir = dir.path().join("tls");
+
+        self_signed(&cache_dir, "localhost").unwrap();
+
+        let metadata = std::fs::metadata(cache_dir.join("localhost.key")).unwrap();
+        assert_eq!(metadata.permissions().mode() & 0o777, 0o600);
+    }
+}
@@ -1,65 +1,285 @@
+use crate::unveil::ProjectInputs;
 use notify::{
-    Config,
+    Config, Event,
     EventKind::{Create, Modify, Remove},
-    RecommendedWatcher, Watcher,
+    RecommendedWatcher, RecursiveMode, Watcher,
 };
 use std::{
-    collections::HashSet,
+    collections::{hash_map::DefaultHasher, BTreeMap, BTreeSet, HashMap},
+    fs,
+    hash::{Hash, Hasher},
     path::{Path, PathBuf},
-    sync::mpsc::channel,
+    sync::mpsc::{Receiver, Sender},
+    time::Duration,
 };
 
-pub fn trigger_on_change<F>(closure: F)
-where
-    F: Fn(&HashSet<PathBuf>),
-{
-    use notify::RecursiveMode::*;
+/// Quiet period closing a batch of events, editors saving through temporary files emit several
+const DEBOUNCE: Duration = Duration::from_millis(150);
 
-    // Create a channel to receive the events.
-    let (tx, rx) = channel();
-    let mut watcher = RecommendedWatcher::new(tx, Config::default()).unwrap();
+/// Messages received by the watching loop
+pub enum WatchEvent {
+    Fs(notify::Result<Event>),
+    /// Leave the loop, sent when the server shuts down
+    Stop,
+}
 
-    // Add the source directory to the watcher
-    if let Err(e) = watcher.watch(Path::new("slides"), Recursive) {
-        eprintln!("Error while watching slides directory/ :\n    {:?}", e);
-        std::process::exit(1);
+/// Rebuild when one of the project inputs changes, the closure receiving the changed files
+/// relative to the project root and returning the inputs of the new build.
+/// Builds run on the watching thread so they never overlap, events received meanwhile
+/// are batched for the next one. Returns once `WatchEvent::Stop` is sent on `events`.
+pub fn trigger_on_change<F>(
+    mut inputs: ProjectInputs,
+    (tx, rx): (Sender<WatchEvent>, Receiver<WatchEvent>),
+    mut closure: F,
+) where
+    F: FnMut(&BTreeSet<PathBuf>) -> ProjectInputs,
+{
+    let handler = move |event: notify::Result<Event>| {
+        let _ = tx.send(WatchEvent::Fs(event));
     };
+    let mut watcher = RecommendedWatcher::new(handler, Config::default()).unwrap();
+    let root = std::env::current_dir().unwrap_or_default();
+
+    let mut watched = BTreeMap::new();
+    update_watches(&mut watcher, &mut watched, &inputs);
 
-    // Watch changes on unveil.toml config and default CSS
-    let _ = watcher.watch(Path::new("public/unveil.css"), NonRecursive);
-    let _ = watcher.watch(Path::new("unveil.toml"), NonRecursive);
+    let mut fingerprints = Fingerprints::default();
+    fingerprints.track(&root, &inputs);
 
     println!("Listening for changes...");
-    let mut paths = HashSet::new();
-
-    for events in rx {
-        match events {
-            Err(e) => eprintln!("Failed to reload project: {}", e),
-            Ok(event) => {
-                let is_style = event.paths.iter().any(|path| path.ends_with("unveil.css"));
-                let is_config = event.paths.iter().any(|path| path.ends_with("unveil.toml"));
-                match event.kind {
-                    // Since we are not watching for the whole project directory,
-                    // file watchers are dropped on modification so we have to recreate them.
-                    Remove(_) if is_config || is_style => {
-                        let css = is_style;
-                        let config = is_config;
-                        if css {
-                            let _ = watcher.watch(Path::new("public/unveil.css"), NonRecursive);
-                            paths.extend(event.paths)
-                        } else if config {
-                            let _ = watcher.watch(Path::new("unveil.toml"), NonRecursive);
-                            paths.extend(event.paths)
-                        }
-                    }
-                    Create(_) | Modify(_) | Remove(_) => paths.extend(event.paths),
-                    _ => {}
-                }
+
+    while let Ok(WatchEvent::Fs(event)) = rx.recv() {
+        let mut paths = BTreeSet::new();
+        collect(&mut paths, event, &root, &inputs);
+        loop {
+            match rx.recv_timeout(DEBOUNCE) {
+                Ok(WatchEvent::Fs(event)) => collect(&mut paths, event, &root, &inputs),
+                Ok(WatchEvent::Stop) => return,
+                Err(_) => break,
+            }
+        }
+
+        let changed = fingerprints.changed(&root, paths);
+        if changed.is_empty() {
+            continue;
+        }
+
+        inputs = closure(&changed);
+        update_watches(&mut watcher, &mut watched, &inputs);
+        fingerprints.track(&root, &inputs);
+    }
+}
+
+/// Keep the input paths touched by an event, relative to the project root
+fn collect(
+    paths: &mut BTreeSet<PathBuf>,
+    event: notify::Result<Event>,
+    root: &Path,
+    inputs: &ProjectInputs,
+) {
+    match event {
+        Err(e) => eprintln!("Failed to reload project: {}", e),
+        Ok(event) => {
+            if let Create(_) | Modify(_) | Remove(_) = event.kind {
+                // Parent directories of the input files are watched, skip their other files
+                paths.extend(
+                    event
+                        .paths
+                        .iter()
+                        .map(|path| path.strip_prefix(root).unwrap_or(path).to_path_buf())
+                        .filter(|path| inputs.contains(path)),
+                );
+            }
+        }
+    }
+}
+
+/// Content hash of the input files, telling real edits from events leaving a file as it was
+#[derive(Default)]
+struct Fingerprints {
+    files: HashMap<PathBuf, Option<u64>>,
+}
+
+impl Fingerprints {
+    /// Record the inputs seen for the first time
+    fn track(&mut self, root: &Path, inputs: &ProjectInputs) {
+        let mut files: Vec<PathBuf> = inputs.files.iter().cloned().collect();
+        for dir in inputs.dirs.iter() {
+            list_files(&root.join(dir), &mut files);
+        }
+
+        for file in files {
+            let file = file.strip_prefix(root).unwrap_or(&file).to_path_buf();
+            let path = root.join(&file);
+            self.files
+                .entry(file)
+                .or_insert_with(|| content_hash(&path));
+        }
+    }
+
+    /// Keep the paths whose content differs from the recorded one and record it
+    fn changed(&mut self, root: &Path, paths: BTreeSet<PathBuf>) -> BTreeSet<PathBuf> {
+        paths
+            .into_iter()
+            .filter(|path| {
+                let hash = content_hash(&root.join(path));
+                // Temporary files created and removed within a batch were never recorded
+                let previous = self.files.insert(path.clone(), hash).flatten();
+                previous != hash
+            })
+            .collect()
+    }
+}
+
+fn list_files(dir: &Path, files: &mut Vec<PathBuf>) {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return;
+    };
+
+    for path in entries.flatten().map(|entry| entry.path()) {
+        if path.is_dir() {
+            list_files(&path, files);
+        } else {
+            files.push(path);
+        }
+    }
+}
+
+fn content_hash(path: &Path) -> Option<u64> {
+    let content = fs::read(path).ok()?;
+    let mut hasher = DefaultHasher::new();
+    content.hash(&mut hasher);
+    Some(hasher.finish())
+}
+
+/// Watch the new targets and drop the ones that are no longer inputs
+fn update_watches(
+    watcher: &mut RecommendedWatcher,
+    watched: &mut BTreeMap<PathBuf, bool>,
+    inputs: &ProjectInputs,
+) {
+    let targets = watch_targets(inputs);
+
+    for (path, recursive) in watched.iter() {
+        if targets.get(path) != Some(recursive) {
+            let _ = watcher.unwatch(path);
+        }
+    }
+    watched.retain(|path, recursive| targets.get(path) == Some(&*recursive));
+
+    for (path, recursive) in targets {
+        if watched.contains_key(&path) {
+            continue;
+        }
+
+        let mode = if recursive {
+            RecursiveMode::Recursive
+        } else {
+            RecursiveMode::NonRecursive
+        };
+
+        match watcher.watch(&path, mode) {
+            Ok(()) => {
+                watched.insert(path, recursive);
             }
+            Err(e) => eprintln!("Unable to watch {} : {}", path.display(), e),
         }
+    }
+}
+
+/// Directories to watch and whether to watch them recursively. Files are watched
+/// through their parent directory so they are still seen after editors replace them.
+fn watch_targets(inputs: &ProjectInputs) -> BTreeMap<PathBuf, bool> {
+    let mut targets = BTreeMap::new();
 
-        if !paths.is_empty() {
-            closure(&paths);
+    for file in inputs.files.iter() {
+        let parent = match file.parent() {
+            Some(parent) if parent != Path::new("") => parent.to_path_buf(),
+            _ => PathBuf::from("."),
+        };
+
+        if parent.is_dir() {
+            targets.entry(parent).or_insert(false);
         }
     }
+
+    for dir in inputs.dirs.iter().filter(|dir| dir.is_dir()) {
+        targets.insert(dir.clone(), true);
+    }
+
+    targets
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        server::watcher::{watch_targets, Fingerprints},
+        unveil::ProjectInputs,
+    };
+    use std::{
+        collections::BTreeSet,
+        fs,
+        path::{Path, PathBuf},
+    };
+
+    #[test]
+    fn should_watch_input_parents_and_directories() {
+        let mut inputs = ProjectInputs::default();
+        inputs.files.insert("Cargo.toml".into());
+        inputs.files.insert("src/lib.rs".into());
+        inputs.files.insert("missing/theme.css".into());
+        inputs.dirs.insert("src/html".into());
+
+        let targets: Vec<(PathBuf, bool)> = watch_targets(&inputs).into_iter().collect();
+
+        assert_eq!(
+            targets,
+            vec![
+                (".".into(), false),
+                ("src".into(), false),
+                ("src/html".into(), true)
+            ]
+        );
+        assert!(inputs.contains(Path::new("src/html/toc.rs")));
+        assert!(!inputs.contains(Path::new("src/main.rs")));
+    }
+
+    #[test]
+    fn should_only_report_files_whose_content_changed() {
+        let root = tempfile::tempdir().unwrap();
+        fs::create_dir(root.path().join("slides")).unwrap();
+        fs::write(root.path().join("unveil.toml"), "name = \"talk\"").unwrap();
+        fs::write(root.path().join("slides/01.md"), "# One").unwrap();
+
+        let mut inputs = ProjectInputs::default();
+        inputs.files.insert("unveil.toml".into());
+        inputs.dirs.insert("slides".into());
+        let mut fingerprints = Fingerprints::default();
+        fingerprints.track(root.path(), &inputs);
+
+        // Saved without edit, edited, added then removed by an editor, created
+        fs::write(root.path().join("unveil.toml"), "name = \"talk\"").unwrap();
+        fs::write(root.path().join("slides/01.md"), "# Intro").unwrap();
+        fs::write(root.path().join("slides/02.md"), "# Two").unwrap();
+        let paths: BTreeSet<PathBuf> = [
+            "unveil.toml",
+            "slides/01.md",
+            "slides/01.md~",
+            "slides/02.md",
+        ]
+        .iter()
+        .map(PathBuf::from)
+        .collect();
+
+        let changed: Vec<PathBuf> = fingerprints
+            .changed(root.path(), paths.clone())
+            .into_iter()
+            .collect();
+
+        assert_eq!(
+            changed,
+            vec![PathBuf::from("slides/01.md"), PathBuf::from("slides/02.md")]
+        );
+        assert!(fingerprints.changed(root.path(), paths).is_empty());
+    }
 }
@@ -2,13 +2,20 @@ use anyhow::{anyhow, Result};
 use std::{
     fs,
     fs::{File, OpenOptions},
-    io::{Read, Write},
+    io::Write,
 };
 
 use crate::{
-    assets::{CSS, HIGHLIGHT_CSS, HIGHLIGHT_JS, JS, LANDING, LIVERELOAD_JS},
+    assets::{
+        CSS, HIGHLIGHT_CSS, HIGHLIGHT_JS, INDEX_CSS, JS, LANDING, LIVERELOAD_JS, PRINT_CSS,
+        SLIDES_CSS,
+    },
     config::UnveilConfig,
+    document::Document,
+    export,
+    export::ExportFormat,
     helper,
+    helper::minify::Minifier,
 };
 
 use crate::{
@@ -19,15 +26,128 @@ use crate::{
         FONT_AWESOME_WOFF2, FONT_AWESOME_WOFF2_900, FONT_AWESOME_WOFF2_BRANDS,
         FONT_AWESOME_WOFF_900, FONT_AWESOME_WOFF_BRANDS,
     },
-    html::HtmlBuilder,
+    html,
+    html::{HtmlBuilder, StyleError},
+    images::{self, ImageProcessor},
+    import,
+    import::ImportFormat,
+    manifest::Manifest,
+    present,
     server::Server,
 };
-use std::path::PathBuf;
+use std::{
+    collections::BTreeSet,
+    fmt,
+    path::{Path, PathBuf},
+};
+
+// Generated site and build cache
+const GITIGNORE: &[u8] = b"public\n.unveil\n";
+
+/// Where a deck is written and how it reaches its assets
+struct DeckOutput<'a> {
+    /// Directory of the deck `index.html`
+    dir: String,
+    /// Url of the deck directory, empty for relative urls
+    url: String,
+    /// Url of the assets shared by every deck
+    assets_url: &'a str,
+    title: Option<&'a str>,
+    /// Deck specific theme, written next to the deck `index.html`
+    theme: Option<&'a str>,
+}
+
+/// Bundled assets referenced by the built decks
+#[derive(Default)]
+struct AssetUsage {
+    code: bool,
+    playpen: bool,
+    brand_icons: bool,
+    regular_icons: bool,
+    /// Processed images written to `public/images` by the decks
+    images: BTreeSet<String>,
+}
+
+/// Files and directories read by a build, watched by `unveil serve`
+#[derive(Default, Debug, Clone, PartialEq, Eq)]
+pub struct ProjectInputs {
+    pub files: BTreeSet<PathBuf>,
+    /// Directories whose whole content is an input, new files included
+    pub dirs: BTreeSet<PathBuf>,
+}
+
+impl ProjectInputs {
+    /// Whether a path, relative to the project root, is an input
+    pub fn contains(&self, path: &Path) -> bool {
+        self.files.contains(path) || self.dirs.iter().any(|dir| path.starts_with(dir))
+    }
+
+    pub fn extend(&mut self, other: ProjectInputs) {
+        self.files.extend(other.files);
+        self.dirs.extend(other.dirs);
+    }
+
+    /// Local files linked from the slides, missing ones are watched until they are created
+    fn add_asset(&mut self, src: &str) {
+        let paths = match export::lookup_image(src) {
+            Some(path) => vec![path],
+            None => export::image_candidates(src),
+        };
+        self.files.extend(
+            paths
+                .iter()
+                .map(|path| path.strip_prefix(".").unwrap_or(path).to_path_buf()),
+        );
+    }
+
+    fn add_theme(&mut self, theme: &str) {
+        if !matches!(theme, "default" | "dark") {
+            self.files.insert(PathBuf::from("public").join(theme));
+        }
+    }
+}
+
+/// Build failure caused by a project file, shown in the pages of `unveil serve`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BuildError {
+    /// Offending file, relative to the project root
+    pub file: String,
+    /// 1-based line in `file`, when known
+    pub line: Option<usize>,
+    pub message: String,
+}
+
+impl BuildError {
+    pub fn new<F: Into<String>, M: ToString>(file: F, line: Option<usize>, message: M) -> Self {
+        BuildError {
+            file: file.into(),
+            line,
+            message: message.to_string(),
+        }
+    }
+}
+
+impl fmt::Display for BuildError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.line {
+            Some(line) => write!(f, "{}:{} : {}", self.file, line, self.message),
+            None => write!(f, "{} : {}", self.file, self.message),
+        }
+    }
+}
+
+impl std::error::Error for BuildError {}
 
 pub struct UnveilProject {
     pub root: PathBuf,
     pub markdown: Vec<String>,
     pub livereload: bool,
+    /// Production build: no live reload, minified and only the used assets
+    pub release: bool,
+    /// Write a `manifest.json` describing the slides next to each deck `index.html`
+    pub emit_json: bool,
+    /// Inputs of the last build
+    pub inputs: ProjectInputs,
 }
 
 impl Default for UnveilProject {
@@ -37,168 +157,366 @@ impl Default for UnveilProject {
             root: PathBuf::from("."),
             markdown: vec![],
             livereload: true,
+            release: false,
+            emit_json: false,
+            inputs: ProjectInputs::default(),
         }
     }
 }
 
 impl UnveilProject {
     /// get markdowns slides as strings
-    fn get_markdown_from_file() -> Result<Vec<String>> {
+    fn get_markdown_from_file(slide_dir: &str, slides: &[String]) -> Result<Vec<String>> {
         let mut markdown_contents = vec![];
-        let config = UnveilConfig::from_disk("unveil.toml")?;
 
         // Read slide names from config and lookup the corresponding slide in the
         // slides directory, this allow to order slides rendering
-        for slide_name in config.slides.iter() {
-            let path = PathBuf::from(&format!("slides/{}", slide_name));
-            let mut file = File::open(path)?;
-            let mut contents = String::new();
-            file.read_to_string(&mut contents)?;
+        for slide_name in slides.iter() {
+            let path = format!("{}/{}", slide_dir, slide_name);
+            let contents = fs::read_to_string(&path).map_err(|err| {
+                BuildError::new(&path, None, format!("Unable to read slide, {}", err))
+            })?;
             markdown_contents.push(contents);
         }
         Ok(markdown_contents)
     }
 
     /// Build a assets file from the markdown content located in `slides/`
-    pub fn build(&mut self, server: &Server) -> Result<()> {
-        // Generate html from markdown files in
-        let markdowns = UnveilProject::get_markdown_from_file()?;
-        let mut processor = HtmlBuilder::new(markdowns, self.livereload);
-
-        let (user_css, html) = processor.build()?;
-        let public = PathBuf::from("public");
+    pub fn build(&mut self) -> Result<()> {
+        self.inputs = ProjectInputs::default();
+        self.inputs.files.insert(PathBuf::from("unveil.toml"));
+        self.inputs.files.insert(PathBuf::from("public/unveil.css"));
+        self.inputs.dirs.insert(PathBuf::from("slides"));
 
         // Double check we are actually in an unveil project
         let config = UnveilConfig::from_disk("unveil.toml")?;
+        self.inputs.add_theme(&config.theme);
+        let base_url = config.base_url();
+        let public = PathBuf::from("public");
 
         // User has remove gitignore and we now need to recreate it
         if config.gitignore {
-            helper::fs::write_file(".gitignore", b"public")?;
+            helper::fs::write_file(".gitignore", GITIGNORE)?;
         }
 
         if !public.exists() {
             fs::create_dir("public")?;
         }
 
-        helper::fs::replace("public/index.html", html.as_bytes())?;
-        helper::fs::write_file("public/unveil.js", JS)?;
+        let mut usage = AssetUsage::default();
+
+        if config.decks.is_empty() {
+            let output = DeckOutput {
+                dir: "public".to_string(),
+                url: base_url.clone(),
+                assets_url: &base_url,
+                title: None,
+                theme: None,
+            };
+            self.build_deck(&config, "slides", &config.slides, output, &mut usage)?;
+            helper::fs::remove("public/index.css")?;
+        } else {
+            // Decks are built in `public/{deck}` and share the assets located in `public`
+            let assets_url = if base_url.is_empty() {
+                "../".to_string()
+            } else {
+                base_url.clone()
+            };
+
+            for deck in config.decks.iter() {
+                let output = DeckOutput {
+                    dir: format!("public/{}", deck.name),
+                    url: if base_url.is_empty() {
+                        String::new()
+                    } else {
+                        format!("{}{}/", base_url, deck.name)
+                    },
+                    assets_url: &assets_url,
+                    title: deck.title.as_deref(),
+                    theme: Some(deck.theme.as_deref().unwrap_or(&config.theme)),
+                };
+
+                let slide_dir = format!("slides/{}", deck.name);
+                self.build_deck(&config, &slide_dir, &deck.slides, output, &mut usage)?;
+            }
+
+            let index = html::deck_index(&config.name, &config.decks, &base_url);
+            let index = if self.release {
+                helper::minify::html(index.as_bytes())?
+            } else {
+                index.into_bytes()
+            };
+
+            helper::fs::replace("public/index.html", &index)?;
+            self.write_asset("public/index.css", INDEX_CSS, Some(helper::minify::css))?;
+        }
 
-        if let Some(css) = user_css {
-            helper::fs::replace("public/user_css.css", css.as_bytes())?;
+        // Processed images of removed or edited pictures
+        if config.images.is_some() {
+            images::prune(&usage.images)?;
         }
 
-        helper::fs::write_file("public/highlight.css", HIGHLIGHT_CSS)?;
-        helper::fs::write_file("public/livereload.js", LIVERELOAD_JS)?;
+        self.write_asset("public/unveil.js", JS, Some(helper::minify::js))?;
+        self.write_asset("public/slides.css", SLIDES_CSS, Some(helper::minify::css))?;
+        self.write_asset("public/print.css", PRINT_CSS, Some(helper::minify::css))?;
+
+        // Release builds skip the assets the slides do not use
+        let has_code = !self.release || usage.code;
+        let has_playpen = !self.release || usage.playpen;
+        let has_brand_icons = !self.release || usage.brand_icons;
+        let has_regular_icons = !self.release || usage.regular_icons;
+
+        if has_code {
+            self.write_asset(
+                "public/highlight.css",
+                HIGHLIGHT_CSS,
+                Some(helper::minify::css),
+            )?;
+            self.write_asset("public/highlight.js", HIGHLIGHT_JS, None)?;
+        } else {
+            helper::fs::remove("public/highlight.css")?;
+            helper::fs::remove("public/highlight.js")?;
+        }
 
-        // Replace livereload.js in case changes were made to the ws host and port
-        let livereload = format!(
-            r#"let socket = new WebSocket("ws://{}:{}");{}"#,
-            server.hostname,
-            server.ws_port,
-            String::from_utf8(LIVERELOAD_JS.to_vec()).unwrap()
-        );
-        helper::fs::replace("public/livereload.js", livereload.as_bytes())?;
+        if has_playpen {
+            self.write_asset("public/clipboard.js", CLIPBOARD_JS, None)?;
+        } else {
+            helper::fs::remove("public/clipboard.js")?;
+        }
 
-        helper::fs::write_file("public/clipboard.js", CLIPBOARD_JS)?;
-        helper::fs::write_file("public/highlight.js", HIGHLIGHT_JS)?;
+        if self.livereload && !self.release {
+            helper::fs::replace("public/livereload.js", LIVERELOAD_JS)?;
+        } else {
+            helper::fs::remove("public/livereload.js")?;
+        }
 
         helper::fs::create_dir("public/fontawesome");
         helper::fs::create_dir("public/fontawesome/webfonts");
         helper::fs::create_dir("public/fontawesome/css");
 
-        helper::fs::write_file(
-            "public/fontawesome/webfonts/fa-regular-400.eot",
-            FONT_AWESOME_EOT,
-        )?;
-        helper::fs::write_file(
-            "public/fontawesome/webfonts/fa-regular-400.svg",
-            FONT_AWESOME_SVG,
-        )?;
-        helper::fs::write_file(
-            "public/fontawesome/webfonts/fa-regular-400.ttf",
-            FONT_AWESOME_TTF,
-        )?;
-        helper::fs::write_file(
-            "public/fontawesome/webfonts/fa-regular-400.woff",
-            FONT_AWESOME_WOFF,
-        )?;
-        helper::fs::write_file(
-            "public/fontawesome/webfonts/fa-regular-400.woff2",
-            FONT_AWESOME_WOFF2,
-        )?;
-
-        helper::fs::write_file(
-            "public/fontawesome/webfonts/fa-brands-400.eot",
-            FONT_AWESOME_EOT_BRANDS,
-        )?;
-        helper::fs::write_file(
-            "public/fontawesome/webfonts/fa-brands-400.svg",
-            FONT_AWESOME_SVG_BRANDS,
-        )?;
-        helper::fs::write_file(
-            "public/fontawesome/webfonts/fa-brands-400.ttf",
-            FONT_AWESOME_TTF_BRANDS,
-        )?;
-        helper::fs::write_file(
-            "public/fontawesome/webfonts/fa-brands-400.woff",
-            FONT_AWESOME_WOFF_BRANDS,
-        )?;
-        helper::fs::write_file(
-            "public/fontawesome/webfonts/fa-brands-400.woff2",
-            FONT_AWESOME_WOFF2_BRANDS,
-        )?;
-
-        helper::fs::write_file(
-            "public/fontawesome/webfonts/fa-solid-900.eot",
-            FONT_AWESOME_EOT_900,
-        )?;
-        helper::fs::write_file(
-            "public/fontawesome/webfonts/fa-solid-900.svg",
-            FONT_AWESOME_SVG_900,
-        )?;
-        helper::fs::write_file(
-            "public/fontawesome/webfonts/fa-solid-900.ttf",
-            FONT_AWESOME_TTF_900,
-        )?;
-        helper::fs::write_file(
-            "public/fontawesome/webfonts/fa-solid-900.woff",
-            FONT_AWESOME_WOFF_900,
-        )?;
-        helper::fs::write_file(
-            "public/fontawesome/webfonts/fa-solid-900.woff2",
-            FONT_AWESOME_WOFF2_900,
-        )?;
-        helper::fs::write_file("public/fontawesome/css/fontawesome.css", FONT_AWESOME)?;
-
-        // We don't overwrite CSS by default
+        let solid = [
+            ("fa-solid-900.eot", FONT_AWESOME_EOT_900),
+            ("fa-solid-900.svg", FONT_AWESOME_SVG_900),
+            ("fa-solid-900.ttf", FONT_AWESOME_TTF_900),
+            ("fa-solid-900.woff", FONT_AWESOME_WOFF_900),
+            ("fa-solid-900.woff2", FONT_AWESOME_WOFF2_900),
+        ];
+
+        let regular = [
+            ("fa-regular-400.eot", FONT_AWESOME_EOT),
+            ("fa-regular-400.svg", FONT_AWESOME_SVG),
+            ("fa-regular-400.ttf", FONT_AWESOME_TTF),
+            ("fa-regular-400.woff", FONT_AWESOME_WOFF),
+            ("fa-regular-400.woff2", FONT_AWESOME_WOFF2),
+        ];
+
+        let brands = [
+            ("fa-brands-400.eot", FONT_AWESOME_EOT_BRANDS),
+            ("fa-brands-400.svg", FONT_AWESOME_SVG_BRANDS),
+            ("fa-brands-400.ttf", FONT_AWESOME_TTF_BRANDS),
+            ("fa-brands-400.woff", FONT_AWESOME_WOFF_BRANDS),
+            ("fa-brands-400.woff2", FONT_AWESOME_WOFF2_BRANDS),
+        ];
+
+        // Solid icons are always used by the navigation arrows
+        for (fonts, used) in [
+            (solid, true),
+            (regular, has_regular_icons),
+            (brands, has_brand_icons),
+        ] {
+            for (filename, content) in fonts {
+                let filename = format!("public/fontawesome/webfonts/{}", filename);
+                if used {
+                    helper::fs::replace(&filename, content)?;
+                } else {
+                    helper::fs::remove(&filename)?;
+                }
+            }
+        }
+
+        // Font urls are relative to the stylesheet unless a base url is set
+        let font_awesome = if base_url.is_empty() {
+            FONT_AWESOME.to_vec()
+        } else {
+            String::from_utf8_lossy(FONT_AWESOME)
+                .replace(
+                    "../webfonts/",
+                    &format!("{}fontawesome/webfonts/", base_url),
+                )
+                .into_bytes()
+        };
+
+        let font_awesome = if self.release {
+            helper::minify::css(&font_awesome)?
+        } else {
+            font_awesome
+        };
+        helper::fs::replace("public/fontawesome/css/fontawesome.css", &font_awesome)?;
+
+        // We don't overwrite CSS by default, it is edited by users and left as is in release
         if !PathBuf::from("public/unveil.css").exists() {
             let mut css = File::create("public/unveil.css")?;
             css.write_all(CSS)?;
         }
 
         // get user theme or the default one
-        let mut theme = File::create("public/theme.css")?;
         let theme_content = config.get_theme()?;
-        theme.write_all(theme_content.as_slice())?;
+        let theme_content = if self.release {
+            helper::minify::css(&theme_content)?
+        } else {
+            theme_content
+        };
+        helper::fs::replace("public/theme.css", &theme_content)?;
 
         Ok(())
     }
 
-    /// Initialize a template project
-    pub fn init(&mut self, project_name: Option<&str>) -> Result<()> {
-        let project_name = if let Some(project_name) = project_name {
-            project_name
+    /// Build a single deck `index.html` and its own stylesheets
+    fn build_deck(
+        &mut self,
+        config: &UnveilConfig,
+        slide_dir: &str,
+        slides: &[String],
+        output: DeckOutput,
+        usage: &mut AssetUsage,
+    ) -> Result<()> {
+        // Generate html from markdown files in
+        let markdowns = UnveilProject::get_markdown_from_file(slide_dir, slides)?;
+        let sources: Vec<(String, String)> = slides
+            .iter()
+            .map(|slide| format!("{}/{}", slide_dir, slide))
+            .zip(markdowns.iter().cloned())
+            .collect();
+        let title = output.title.unwrap_or(&config.name);
+        let manifest = Manifest::new(title, &sources);
+
+        // Local images and files linked from the slides
+        for asset in manifest.slides.iter().flat_map(|slide| slide.assets.iter()) {
+            self.inputs.add_asset(asset);
+        }
+
+        if let Some(theme) = output.theme {
+            self.inputs.add_theme(theme);
+        }
+
+        if self.emit_json {
+            let manifest = serde_json::to_string_pretty(&manifest)?;
+            helper::fs::create_dir(&output.dir);
+            helper::fs::replace(
+                &format!("{}/manifest.json", output.dir),
+                manifest.as_bytes(),
+            )?;
+        }
+
+        let mut processor = HtmlBuilder::new(markdowns, self.livereload && !self.release)
+            .with_release(self.release)
+            .with_base_url(&output.url)
+            .with_assets_url(output.assets_url)
+            .with_deck_theme(output.theme.is_some())
+            .with_slide_names(slides.iter().map(|slide| slide_name(slide)).collect())
+            .with_toc_highlight(config.toc.as_ref().is_some_and(|toc| toc.highlight_current));
+
+        if let Some(title) = output.title {
+            processor = processor.with_title(title);
+        }
+
+        let (user_css, html) = processor.build().map_err(|err| {
+            match err.downcast::<StyleError>() {
+                Ok(style) => {
+                    let (file, source) = &sources[style.slide];
+                    // The style matter starts on the line after the opening `+++`
+                    let opening = source.lines().position(|line| line.trim() == "+++");
+                    let line = style
+                        .line
+                        .zip(opening)
+                        .map(|(line, opening)| opening + 1 + line);
+                    BuildError::new(file, line, style.message).into()
+                }
+                Err(err) => err,
+            }
+        })?;
+        let print = processor.build_print(&config.print.clone().unwrap_or_default())?;
+
+        let images = config
+            .images
+            .as_ref()
+            .map(|images| ImageProcessor::new(images.clone(), output.assets_url));
+
+        let (html, print) = match images {
+            Some(mut images) => {
+                let rewritten = (images.rewrite_html(&html), images.rewrite_html(&print));
+                usage.images.extend(images.written().iter().cloned());
+                rewritten
+            }
+            None => (html, print),
+        };
+
+        usage.code |= processor.has_code_blocks();
+        usage.playpen |= processor.has_playpen();
+        usage.brand_icons |= html.contains("fab ");
+        usage.regular_icons |= html.contains("far ");
+
+        let (html, print) = if self.release {
+            (
+                helper::minify::html(html.as_bytes())?,
+                helper::minify::html(print.as_bytes())?,
+            )
         } else {
-            "unveil"
+            (html.into_bytes(), print.into_bytes())
         };
 
+        helper::fs::create_dir(&output.dir);
+        helper::fs::replace(&format!("{}/index.html", output.dir), &html)?;
+        helper::fs::replace(&format!("{}/print.html", output.dir), &print)?;
+
+        if let Some(css) = user_css {
+            let css = if self.release {
+                helper::minify::css(css.as_bytes())?
+            } else {
+                css.into_bytes()
+            };
+            helper::fs::replace(&format!("{}/user_css.css", output.dir), &css)?;
+        }
+
+        if let Some(theme) = output.theme {
+            let theme = UnveilConfig::load_theme(theme)?;
+            let theme = if self.release {
+                helper::minify::css(&theme)?
+            } else {
+                theme
+            };
+            helper::fs::replace(&format!("{}/theme.css", output.dir), &theme)?;
+        }
+
+        Ok(())
+    }
+
+    /// Write a bundled asset, always overwriting the previous version so a
+    /// dev build never keeps an outdated or minified one. Release builds
+    /// minify it, minifier being `None` for assets that are already minified.
+    fn write_asset(
+        &self,
+        filename: &str,
+        content: &[u8],
+        minifier: Option<Minifier>,
+    ) -> Result<()> {
+        match minifier {
+            Some(minify) if self.release => helper::fs::replace(filename, &minify(content)?),
+            _ => helper::fs::replace(filename, content),
+        }
+    }
+
+    /// Initialize a template project
+    pub fn init(&mut self, project_name: Option<&str>) -> Result<()> {
+        let project_name = project_name.unwrap_or("unveil");
+
         // Create slides dir
         fs::create_dir(project_name)?;
         fs::create_dir(format!("{}/slides", project_name))?;
 
         // Add default gitignore
         let mut gitignore = File::create(format!("{}/.gitignore", project_name))?;
-        gitignore.write_all(b"public")?;
+        gitignore.write_all(GITIGNORE)?;
 
         // Add a default example slides
         let mut landing = File::create(format!("{}/slides/landing.md", project_name))?;
@@ -217,42 +535,254 @@ impl UnveilProject {
             .map_err(|err| anyhow!("Unable to remove public directory : {}", err))
     }
 
-    pub fn new_slide(&mut self, name: &str) -> Result<()> {
+    pub fn new_slide(&mut self, name: &str, deck: Option<&str>) -> Result<()> {
         let filename = if name.ends_with(".md") {
             name.into()
         } else {
             format!("{}.md", name)
         };
 
-        let path = PathBuf::from("slides").join(&filename);
-
         let mut config = UnveilConfig::from_disk("unveil.toml")?;
 
-        File::create(path).map(|_| ())?;
-        config.slides.push(filename);
+        let (slide_dir, slides) = match deck {
+            Some(deck) => {
+                let deck = config
+                    .decks
+                    .iter_mut()
+                    .find(|candidate| candidate.name == deck)
+                    .ok_or_else(|| anyhow!("No deck named {} in unveil.toml", deck))?;
+                (PathBuf::from("slides").join(&deck.name), &mut deck.slides)
+            }
+            None => (PathBuf::from("slides"), &mut config.slides),
+        };
+
+        fs::create_dir_all(&slide_dir)?;
+        File::create(slide_dir.join(&filename)).map(|_| ())?;
+        slides.push(filename);
+
+        UnveilProject::write_config(&config)
+    }
+
+    /// Convert a Marp, Deckset or reveal.js presentation into slides and
+    /// replace the slide list of `unveil.toml` with them
+    pub fn import(&self, file: &str, format: ImportFormat) -> Result<()> {
+        let source =
+            fs::read_to_string(file).map_err(|err| anyhow!("Unable to read {} : {}", file, err))?;
+
+        let slides = import::import(&source, format);
+        if slides.is_empty() {
+            return Err(anyhow!("No slides found in {}", file));
+        }
+
+        let mut config = if PathBuf::from("unveil.toml").exists() {
+            UnveilConfig::from_disk("unveil.toml")?
+        } else {
+            let name = PathBuf::from(file)
+                .file_stem()
+                .and_then(|stem| stem.to_str())
+                .unwrap_or("unveil")
+                .to_string();
+            UnveilConfig {
+                name,
+                ..UnveilConfig::default()
+            }
+        };
 
+        if !config.decks.is_empty() {
+            return Err(anyhow!(
+                "Importing into a multi-deck project is not supported"
+            ));
+        }
+
+        // Never overwrite existing slides
+        let slide_dir = PathBuf::from("slides");
+        if let Some(existing) = slides
+            .iter()
+            .map(|slide| slide_dir.join(&slide.filename))
+            .find(|path| path.exists())
+        {
+            return Err(anyhow!("{} already exists", existing.display()));
+        }
+
+        fs::create_dir_all(&slide_dir)?;
+        for slide in slides.iter() {
+            fs::write(slide_dir.join(&slide.filename), &slide.markdown)?;
+        }
+
+        config.slides = slides.into_iter().map(|slide| slide.filename).collect();
+        UnveilProject::write_config(&config)?;
+        println!("Imported {} slides from {}", config.slides.len(), file);
+
+        Ok(())
+    }
+
+    fn write_config(config: &UnveilConfig) -> Result<()> {
         let mut file = OpenOptions::new()
             .write(true)
             .create(true)
+            .truncate(true)
             .open("unveil.toml")?;
 
-        file.write_all(toml::to_string(&config)?.as_bytes())
+        file.write_all(toml::to_string(config)?.as_bytes())
             .map_err(|err| anyhow!("Error writing to unveil.toml : {}", err))
     }
 
-    pub async fn serve(
-        &mut self,
-        hostname: Option<&str>,
-        http_port: Option<i32>,
-        ws_port: Option<i32>,
+    /// Export the decks to a standalone document, `output` defaulting to the
+    /// project or deck name with the format extension
+    pub fn export(
+        &self,
+        format: ExportFormat,
+        deck: Option<&str>,
+        output: Option<&str>,
     ) -> Result<()> {
-        let server = Server::default()
-            .with_hostname(hostname)
-            .with_http_port(http_port)
-            .with_ws_port(ws_port);
+        let config = UnveilConfig::from_disk("unveil.toml")?;
+        let page_size = config.print.clone().unwrap_or_default().page_size()?;
+        let documents = UnveilProject::read_documents(&config, deck)?;
+
+        if output.is_some() && documents.len() > 1 {
+            return Err(anyhow!(
+                "--output requires a single deck, use --deck to select one"
+            ));
+        }
+
+        for (name, document) in documents.iter() {
+            let filename = match output {
+                Some(output) => output.to_string(),
+                None => format!("{}.{}", sanitize_filename(name), format.extension()),
+            };
+
+            export::export(document, format, page_size, filename.as_ref())?;
+            println!("Exported {}", filename);
+        }
+
+        Ok(())
+    }
+
+    /// Present the slides in the terminal
+    pub fn present(&self, deck: Option<&str>) -> Result<()> {
+        let config = UnveilConfig::from_disk("unveil.toml")?;
+        let mut documents = UnveilProject::read_documents(&config, deck)?;
+
+        if documents.len() > 1 {
+            return Err(anyhow!("Select the deck to present with --deck"));
+        }
+
+        let (_, document) = documents.remove(0);
+        present::present(&document)
+    }
+
+    /// Read the documents of every deck, or of the single `deck`, named after their deck
+    fn read_documents(
+        config: &UnveilConfig,
+        deck: Option<&str>,
+    ) -> Result<Vec<(String, Document)>> {
+        let mut documents = vec![];
+        if config.decks.is_empty() {
+            if let Some(deck) = deck {
+                return Err(anyhow!("No deck named {} in unveil.toml", deck));
+            }
+
+            let document =
+                UnveilProject::read_document(&config.name, config, "slides", &config.slides)?;
+            documents.push((config.name.clone(), document));
+        } else {
+            for candidate in config.decks.iter() {
+                if deck.is_none_or(|deck| deck == candidate.name) {
+                    let title = candidate.title.as_deref().unwrap_or(&candidate.name);
+                    let slide_dir = format!("slides/{}", candidate.name);
+                    let mut document =
+                        UnveilProject::read_document(title, config, &slide_dir, &candidate.slides)?;
+                    document.author = candidate.author.clone();
+                    document.date = candidate.date.clone();
+                    documents.push((candidate.name.clone(), document));
+                }
+            }
+
+            if documents.is_empty() {
+                return Err(anyhow!(
+                    "No deck named {} in unveil.toml",
+                    deck.unwrap_or_default()
+                ));
+            }
+        }
+
+        Ok(documents)
+    }
+
+    fn read_document(
+        title: &str,
+        config: &UnveilConfig,
+        slide_dir: &str,
+        slides: &[String],
+    ) -> Result<Document> {
+        let markdowns = UnveilProject::get_markdown_from_file(slide_dir, slides)?;
+        let sources: Vec<(String, String)> = slides
+            .iter()
+            .map(|slide| format!("{}/{}", slide_dir, slide))
+            .zip(markdowns)
+            .collect();
+
+        Ok(Document::parse(title, &config.language, &sources))
+    }
+
+    pub async fn serve(&mut self, server: Server) -> Result<()> {
+        self.build()?;
+
+        UnveilProject::configure_server(server)?
+            .serve(self.inputs.clone())
+            .await
+    }
+
+    /// Build for production and serve the result without live reload
+    pub async fn preview(&mut self, server: Server) -> Result<()> {
+        self.release = true;
+        self.build()?;
+
+        UnveilProject::configure_server(server)?.preview().await
+    }
+
+    fn configure_server(server: Server) -> Result<Server> {
+        let config = UnveilConfig::from_disk("unveil.toml")?;
+        let open = server.open && config.serve.as_ref().is_none_or(|serve| serve.open);
+        Ok(server.with_open(open).with_base_url(&config.base_url()))
+    }
+}
+
+/// Turn a project or deck name into a portable file name
+fn sanitize_filename(name: &str) -> String {
+    name.chars()
+        .map(|char| {
+            if char.is_alphanumeric() || char == '-' || char == '_' {
+                char
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
+
+/// Name of a slide in urls, its file name without extension
+pub(crate) fn slide_name(filename: &str) -> String {
+    Path::new(filename)
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().to_string())
+        .unwrap_or_else(|| filename.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::unveil::ProjectInputs;
+    use std::path::Path;
+
+    #[test]
+    fn should_watch_missing_referenced_images() {
+        let mut inputs = ProjectInputs::default();
 
-        self.build(&server)?;
+        inputs.add_asset("/images/not-yet-drawn.png");
+        inputs.add_asset("https://example.com/remote.png");
 
-        server.serve().await
+        assert!(inputs.contains(Path::new("images/not-yet-drawn.png")));
+        assert!(inputs.contains(Path::new("public/images/not-yet-drawn.png")));
+        assert_eq!(inputs.files.len(), 2);
     }
 }